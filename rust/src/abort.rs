@@ -0,0 +1,97 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::sync::watch;
+
+use crate::error::{Error, ErrorKind};
+
+// Global storage for abort tokens handed to JS
+static ABORT_TOKENS: Lazy<StdMutex<HashMap<u64, Arc<AbortToken>>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+static NEXT_ABORT_ID: Lazy<StdMutex<u64>> = Lazy::new(|| StdMutex::new(1));
+
+/// Cancellation flag set from JS (an `AbortSignal` firing) and watched by requests
+#[derive(Debug)]
+pub struct AbortToken {
+    aborted: watch::Sender<bool>,
+}
+
+impl Default for AbortToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AbortToken {
+    pub fn new() -> Self {
+        let (aborted, _) = watch::channel(false);
+        Self { aborted }
+    }
+
+    pub fn abort(&self) {
+        self.aborted.send_replace(true);
+    }
+
+    pub fn is_aborted(&self) -> bool {
+        *self.aborted.borrow()
+    }
+
+    /// Resolve once the token is aborted
+    pub async fn cancelled(&self) {
+        let mut receiver = self.aborted.subscribe();
+        // The sender lives in `self`, so this only returns once aborted
+        let _ = receiver.wait_for(|aborted| *aborted).await;
+    }
+}
+
+/// Run `future` unless `token` is aborted first.
+///
+/// The future is dropped on abort, which closes the connection it was using.
+pub async fn abortable<T>(
+    token: Option<&AbortToken>,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(token) = token else {
+        return future.await;
+    };
+
+    if token.is_aborted() {
+        return Err(aborted_error());
+    }
+
+    tokio::select! {
+        result = future => result,
+        _ = token.cancelled() => Err(aborted_error()),
+    }
+}
+
+fn aborted_error() -> anyhow::Error {
+    Error::new(ErrorKind::Aborted, "This operation was aborted").into()
+}
+
+/// Store an abort token and return its ID
+pub fn store_token(token: Arc<AbortToken>) -> u64 {
+    let mut id_lock = NEXT_ABORT_ID.lock().unwrap();
+    let id = *id_lock;
+    *id_lock += 1;
+    drop(id_lock);
+
+    let mut tokens = ABORT_TOKENS.lock().unwrap();
+    tokens.insert(id, token);
+    id
+}
+
+/// Get an abort token by ID
+pub fn get_token(id: u64) -> Option<Arc<AbortToken>> {
+    let tokens = ABORT_TOKENS.lock().unwrap();
+    tokens.get(&id).cloned()
+}
+
+/// Remove an abort token (requests using it keep their reference)
+pub fn remove_token(id: u64) {
+    let mut tokens = ABORT_TOKENS.lock().unwrap();
+    tokens.remove(&id);
+}
@@ -1,82 +1,723 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::{Lazy, OnceCell};
+use rand::Rng;
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::time::Duration;
-use wreq_util::Emulation;
+use wreq::header::HeaderMap;
 use std::collections::hash_map::Entry;
+use url::Url;
+
+use crate::abort::AbortToken;
+use crate::cookies::{parse_set_cookies, Cookie, CookieJar};
+use crate::error::{Error, ErrorKind, TimeoutPhase};
+use crate::headers::{
+    orig_header_map, with_cookies, with_profile, HeaderList, HeaderOrder, ProfileHeaders,
+};
+use crate::network::NetworkOptions;
+use crate::profile::Profile;
+use crate::proxy::{PickedProxy, ProxyConfig, ProxyOutcome, ProxyPool};
+use crate::stream::{file_body, remove_stream, store_stream, BodyStream, StreamBody};
+use crate::timeout::{run_until, BodyTimeouts, Deadline};
+use crate::tls::TlsOptions;
+
+/// Timeout applied when neither the request nor the client sets one
+pub const DEFAULT_TIMEOUT_MS: u64 = 30000;
+
+/// Redirects followed before giving up, unless the request sets `maxRedirects`
+pub const DEFAULT_MAX_REDIRECTS: usize = 10;
+
+/// Most attempts a retry policy may make, the first one included
+pub const MAX_RETRY_ATTEMPTS: u32 = 10;
+
+// Global Tokio runtime for HTTP requests (configured once through `init`)
+static HTTP_RUNTIME: OnceCell<tokio::runtime::Runtime> = OnceCell::new();
+
+// Global storage for reusable HTTP clients
+static CLIENTS: Lazy<StdMutex<HashMap<u64, HttpClient>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+static NEXT_CLIENT_ID: Lazy<StdMutex<u64>> = Lazy::new(|| StdMutex::new(1));
+
+/// What to do when the server answers with a redirect
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectMode {
+    /// Follow up to `max_redirects` hops
+    Follow,
+    /// Return the redirect response as-is
+    Manual,
+    /// Fail the request
+    Error,
+}
+
+/// Request body source
+#[derive(Debug, Clone)]
+pub enum RequestBody {
+    /// Fully buffered body (string or bytes)
+    Bytes(Vec<u8>),
+    /// File streamed from disk while sending
+    File(PathBuf),
+    /// Chunks written from JS through a `BodySink`
+    Stream(Arc<StreamBody>),
+}
 
 #[derive(Debug, Clone)]
 pub struct RequestOptions {
     pub url: String,
-    pub emulation: Emulation,
-    pub headers: HashMap<String, String>,
+    pub emulation: Profile,
+    pub headers: HeaderList,
+    /// Where custom headers go relative to the profile's defaults
+    pub header_order: HeaderOrder,
+    /// Changes to the profile's default headers
+    pub profile_headers: ProfileHeaders,
     pub method: String,
-    pub body: Option<String>,
-    pub proxy: Option<String>,
-    pub timeout: u64,
+    pub body: Option<RequestBody>,
+    /// Declared body length for streamed bodies (chunked encoding otherwise)
+    pub content_length: Option<u64>,
+    pub proxy: Option<ProxyConfig>,
+    /// Pool every attempt picks its proxy from, instead of `proxy`
+    pub proxy_pool: Option<Arc<ProxyPool>>,
+    /// Total time for the whole request, body included (`totalTimeout` / `timeout`)
+    pub timeout: Option<u64>,
+    /// TCP connect + TLS handshake, only for one-off requests (fixed per client)
+    pub connect_timeout: Option<u64>,
+    /// Local binding and resolver settings, only for one-off requests (fixed per client)
+    pub network: NetworkOptions,
+    /// Trusted roots, client certificate and verification, only for one-off requests
+    /// (fixed per client)
+    pub tls: TlsOptions,
+    /// Time to wait for the response headers of each hop
+    pub response_header_timeout: Option<u64>,
+    /// Maximum idle time between two body chunks
+    pub read_timeout: Option<u64>,
+    /// Resolve once headers arrive and leave the body in a `BodyStream`
+    pub stream: bool,
+    pub redirect: RedirectMode,
+    pub max_redirects: usize,
+    /// Jar to read and store cookies in instead of the client's own
+    pub cookie_jar: Option<Arc<CookieJar>>,
+    /// Cancels the request (and a streamed body) when aborted from JS
+    pub abort: Option<Arc<AbortToken>>,
+    /// Retry failed attempts (a single attempt without it)
+    pub retry: Option<RetryPolicy>,
+}
+
+impl RequestOptions {
+    /// Client settings for a one-off request that doesn't go through a stored client
+    pub fn client_options(&self) -> ClientOptions {
+        ClientOptions {
+            emulation: self.emulation.clone(),
+            proxy: self.proxy.clone(),
+            proxy_pool: self.proxy_pool.clone(),
+            timeout: Some(DEFAULT_TIMEOUT_MS),
+            connect_timeout: self.connect_timeout,
+            network: self.network.clone(),
+            tls: self.tls.clone(),
+            response_header_timeout: None,
+            read_timeout: None,
+            cookie_jar: None,
+        }
+    }
+}
+
+/// When and how often a failed request is sent again
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts in total, the first one included
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled for every further one
+    pub base_delay_ms: u64,
+    /// Upper bound for a backoff delay (and for an honoured `Retry-After`)
+    pub max_delay_ms: u64,
+    /// Randomize delays between half and all of the backoff
+    pub jitter: bool,
+    /// Response statuses that are retried
+    pub statuses: Vec<u16>,
+    /// Retry timeouts and refused/reset connections
+    pub retry_on_error: bool,
+    /// Also retry methods that are not idempotent (POST, PATCH, ...)
+    pub non_idempotent: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay_ms: 200,
+            max_delay_ms: 10_000,
+            jitter: true,
+            statuses: vec![408, 429, 500, 502, 503, 504],
+            retry_on_error: true,
+            non_idempotent: false,
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn allows_method(&self, method: &wreq::Method) -> bool {
+        self.non_idempotent
+            || matches!(
+                *method,
+                wreq::Method::GET
+                    | wreq::Method::HEAD
+                    | wreq::Method::OPTIONS
+                    | wreq::Method::TRACE
+                    | wreq::Method::PUT
+                    | wreq::Method::DELETE
+            )
+    }
+
+    /// Exponential backoff before attempt `attempt + 1`
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .base_delay_ms
+            .saturating_mul(1u64 << exponent)
+            .min(self.max_delay_ms);
+
+        let delay = if self.jitter && delay > 0 {
+            rand::rng().random_range(delay / 2..=delay)
+        } else {
+            delay
+        };
+
+        Duration::from_millis(delay)
+    }
+
+    /// Delay before retrying a response, `None` if it should be returned as-is
+    fn delay_after_response(&self, response: &Response, attempt: u32) -> Option<Duration> {
+        if !self.statuses.contains(&response.status) {
+            return None;
+        }
+
+        // 429 and 503 may say when to come back; waits beyond the cap are not retried
+        if matches!(response.status, 429 | 503) {
+            let delay = response
+                .headers
+                .get("retry-after")
+                .and_then(|values| values.first())
+                .and_then(|value| retry_after(value));
+            if let Some(delay) = delay {
+                return (delay <= Duration::from_millis(self.max_delay_ms)).then_some(delay);
+            }
+        }
+
+        Some(self.backoff(attempt))
+    }
+
+    /// Delay before retrying a failed attempt, `None` if the error is final
+    fn delay_after_error(&self, error: &anyhow::Error, attempt: u32) -> Option<Duration> {
+        if !self.retry_on_error {
+            return None;
+        }
+
+        let error = Error::from_anyhow(error);
+        let retryable = match error.kind {
+            // The total timeout spans every attempt, so it can't be retried
+            ErrorKind::Timeout => error.phase != Some(TimeoutPhase::Total),
+            ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset => true,
+            _ => false,
+        };
+
+        retryable.then(|| self.backoff(attempt))
+    }
+}
+
+// `Retry-After` value as delay-seconds or an HTTP date
+fn retry_after(value: &str) -> Option<Duration> {
+    let value = value.trim();
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(
+        date.duration_since(std::time::SystemTime::now())
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Settings fixed for the lifetime of a client (and its connection pool)
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    pub emulation: Profile,
+    pub proxy: Option<ProxyConfig>,
+    /// Pool the client's requests rotate through, instead of `proxy`
+    pub proxy_pool: Option<Arc<ProxyPool>>,
+    /// Default total timeout for requests that don't set their own
+    pub timeout: Option<u64>,
+    /// TCP connect + TLS handshake timeout for every connection of the client
+    pub connect_timeout: Option<u64>,
+    /// Local address or interface the client's connections leave from, and
+    /// the order resolved addresses are tried in
+    pub network: NetworkOptions,
+    /// Extra root certificates, client certificate and verification of the client's connections
+    pub tls: TlsOptions,
+    /// Default `response_header_timeout` of the client's requests
+    pub response_header_timeout: Option<u64>,
+    /// Default `read_timeout` of the client's requests
+    pub read_timeout: Option<u64>,
+    /// Jar shared with other clients (a fresh one is created otherwise)
+    pub cookie_jar: Option<Arc<CookieJar>>,
+}
+
+/// A built client plus the per-request defaults it was created with
+#[derive(Clone)]
+pub struct HttpClient {
+    client: wreq::Client,
+    timeout: Option<u64>,
+    response_header_timeout: Option<u64>,
+    read_timeout: Option<u64>,
+    cookie_jar: Arc<CookieJar>,
+    /// Default headers of the emulation profile, for requests that change them
+    profile_headers: HeaderMap,
+    /// Name of the profile the client impersonates
+    profile: String,
+    /// Proxies the client's requests are routed through by scheme and host
+    proxy: Option<ProxyConfig>,
+    /// Pool each request picks its proxy from
+    proxy_pool: Option<Arc<ProxyPool>>,
+}
+
+/// One redirect response seen on the way to the final response
+#[derive(Debug, Clone)]
+pub struct RedirectHop {
+    pub url: String,
+    pub status: u16,
+    /// Resolved target of the `Location` header
+    pub location: String,
+    pub set_cookies: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
 pub struct Response {
     pub status: u16,
     pub headers: HashMap<String, Vec<String>>,
-    pub body: String,
-    pub cookies: HashMap<String, String>,
+    pub body: Vec<u8>,
+    pub cookies: Vec<Cookie>,
     pub url: String,
+    /// Redirects followed before the final response, in order
+    pub redirects: Vec<RedirectHop>,
+    /// ID of the `BodyStream` holding the body when the request was streamed
+    pub stream_id: Option<u64>,
+    /// Attempts made, retries included
+    pub attempts: u32,
+    /// Name of the browser profile the request impersonated
+    pub profile: String,
+    /// Proxy the request went through (the one picked from a pool)
+    pub proxy: Option<String>,
 }
 
-pub async fn make_request(options: RequestOptions) -> Result<Response> {
-    // Create client builder with emulation
+fn build_runtime(worker_threads: Option<usize>) -> Result<tokio::runtime::Runtime> {
+    let mut builder = tokio::runtime::Builder::new_multi_thread();
+    builder.enable_all().thread_name("node-wreq-http");
+
+    if let Some(worker_threads) = worker_threads {
+        builder.worker_threads(worker_threads);
+    }
+
+    builder
+        .build()
+        .context("Failed to create Tokio runtime for HTTP requests")
+}
+
+/// Configure the HTTP runtime; must run before the first request
+pub fn init_runtime(worker_threads: Option<usize>) -> Result<()> {
+    if worker_threads == Some(0) {
+        return Err(anyhow!("workerThreads must be at least 1"));
+    }
+
+    if HTTP_RUNTIME.get().is_some() {
+        return Err(anyhow!(
+            "HTTP runtime is already initialized; call init() before the first request"
+        ));
+    }
+
+    let runtime = build_runtime(worker_threads)?;
+    HTTP_RUNTIME.set(runtime).map_err(|_| {
+        anyhow!("HTTP runtime is already initialized; call init() before the first request")
+    })
+}
+
+/// Shared runtime all HTTP requests run on (lazily created with defaults)
+pub fn http_runtime() -> &'static tokio::runtime::Runtime {
+    HTTP_RUNTIME.get_or_init(|| {
+        build_runtime(None).expect("Failed to create Tokio runtime for HTTP requests")
+    })
+}
+
+/// Build a client with emulation and cookie jar applied (proxies are picked per request)
+pub fn build_client(options: &ClientOptions) -> Result<HttpClient> {
+    // Keep the profile's headers for requests that replace or remove some of them
+    let mut emulation = options.emulation.emulation();
+    let profile_headers = emulation.headers_mut().clone();
+
+    // Create client builder with emulation (cookies are handled by our jar); the
+    // system proxy stays off, env vars only apply through `ProxyConfig` with `fromEnv`
     let mut client_builder = wreq::Client::builder()
-        .emulation(options.emulation)
-        .cookie_store(true);
+        .emulation(emulation)
+        .redirect(wreq::redirect::Policy::none())
+        .no_proxy();
 
-    // Apply proxy if present (must be set at client builder level)
-    if let Some(proxy_url) = &options.proxy {
-        let proxy = wreq::Proxy::all(proxy_url)
-            .context("Failed to create proxy")?;
-        client_builder = client_builder.proxy(proxy);
+    // Connect timeout covers the TCP connect and TLS handshake of new connections
+    if let Some(connect_timeout) = options.connect_timeout {
+        client_builder = client_builder.connect_timeout(Duration::from_millis(connect_timeout));
     }
 
+    // Bind connections to a local address or interface, set up host name resolution
+    client_builder = options.network.configure(
+        client_builder,
+        &options.emulation,
+        &options.tls,
+        options.proxy.as_ref(),
+        options.proxy_pool.as_deref(),
+    )?;
+
+    // Trust extra roots, present a client certificate, or skip verification
+    client_builder = options.tls.configure(client_builder)?;
+
     // Build the client
     let client = client_builder
         .build()
         .context("Failed to build HTTP client")?;
 
+    // Other timeouts are applied per request so streamed bodies can opt out of them
+    Ok(HttpClient {
+        client,
+        timeout: options.timeout,
+        response_header_timeout: options.response_header_timeout,
+        read_timeout: options.read_timeout,
+        cookie_jar: options.cookie_jar.clone().unwrap_or_default(),
+        profile_headers,
+        profile: options.emulation.name().to_string(),
+        proxy: options.proxy.clone(),
+        proxy_pool: options.proxy_pool.clone(),
+    })
+}
+
+/// Store a client and return its ID
+pub fn store_client(client: HttpClient) -> u64 {
+    let mut id_lock = NEXT_CLIENT_ID.lock().unwrap();
+    let id = *id_lock;
+    *id_lock += 1;
+    drop(id_lock);
+
+    let mut clients = CLIENTS.lock().unwrap();
+    clients.insert(id, client);
+    id
+}
+
+/// Get a client by ID (cheap clone sharing the same connection pool)
+pub fn get_client(id: u64) -> Option<HttpClient> {
+    let clients = CLIENTS.lock().unwrap();
+    clients.get(&id).cloned()
+}
+
+/// Remove a client, dropping its pool once in-flight requests finish
+pub fn remove_client(id: u64) {
+    let mut clients = CLIENTS.lock().unwrap();
+    clients.remove(&id);
+}
+
+/// Send a request, retrying it as its `RetryPolicy` allows
+pub async fn make_request(http_client: &HttpClient, options: RequestOptions) -> Result<Response> {
+    // Total timeout covers the whole body, so streams only get an explicit one
+    let total_timeout = match options.timeout {
+        Some(timeout) => Some(timeout),
+        None if options.stream => None,
+        None => http_client.timeout,
+    };
+    let total = Deadline::after(total_timeout, TimeoutPhase::Total);
+
+    // Any valid token is accepted (OPTIONS, TRACE, PROPFIND, REPORT, ...)
     let method = if options.method.is_empty() {
         "GET"
     } else {
         &options.method
     };
+    let method = wreq::Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|_| {
+        Error::new(
+            ErrorKind::InvalidRequest,
+            format!("Invalid HTTP method: {}", method),
+        )
+    })?;
 
-    // Build request
-    let mut request = match method.to_uppercase().as_str() {
-        "GET" => client.get(&options.url),
-        "POST" => client.post(&options.url),
-        "PUT" => client.put(&options.url),
-        "DELETE" => client.delete(&options.url),
-        "PATCH" => client.patch(&options.url),
-        "HEAD" => client.head(&options.url),
-        _ => return Err(anyhow::anyhow!("Unsupported HTTP method: {}", method)),
+    let retry = match &options.retry {
+        Some(retry) => retry.clone(),
+        None => return send_through_proxy(http_client, &method, options, total).await,
     };
 
-    // Apply custom headers
-    for (key, value) in &options.headers {
-        request = request.header(key, value);
-    }
+    // A streamed request body can only be sent once
+    let replayable = !matches!(options.body, Some(RequestBody::Stream(_)));
+    let can_retry = replayable && retry.allows_method(&method);
+
+    let mut attempt = 1;
+    loop {
+        let result = send_through_proxy(http_client, &method, options.clone(), total).await;
+
+        let delay = if can_retry && attempt < retry.max_attempts {
+            match &result {
+                Ok(response) => retry.delay_after_response(response, attempt),
+                Err(error) => retry.delay_after_error(error, attempt),
+            }
+        } else {
+            None
+        };
 
-    // Apply body if present
-    if let Some(body) = options.body {
-        request = request.body(body);
+        // No point waiting if the total timeout runs out first
+        let delay = delay.filter(|delay| total.is_none_or(|total| *delay < total.remaining()));
+
+        match delay {
+            Some(delay) => {
+                // Drop the unread body of a streamed response before trying again
+                if let Ok(Response {
+                    stream_id: Some(stream_id),
+                    ..
+                }) = &result
+                {
+                    remove_stream(*stream_id);
+                }
+
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            None => {
+                return result.map(|response| Response {
+                    attempts: attempt,
+                    ..response
+                })
+            }
+        }
     }
+}
+
+// One attempt through the next proxy of the client's pool, whose health is
+// updated with the outcome
+async fn send_through_proxy(
+    http_client: &HttpClient,
+    method: &wreq::Method,
+    options: RequestOptions,
+    total: Option<Deadline>,
+) -> Result<Response> {
+    let Some(pool) = &http_client.proxy_pool else {
+        return send_attempt(http_client, method, options, None, total).await;
+    };
+
+    let host = Url::parse(&options.url)
+        .ok()
+        .and_then(|url| url.host_str().map(|host| host.to_string()))
+        .unwrap_or_default();
+    let proxy = pool.pick(&host)?;
+
+    let result = send_attempt(http_client, method, options, Some(&proxy), total).await;
+
+    let outcome = match &result {
+        Ok(response) => ProxyOutcome::of_status(response.status),
+        Err(error) => ProxyOutcome::of_error(error),
+    };
+    pool.record(&proxy.url, outcome);
+
+    result
+}
+
+// One attempt: send the request, following redirects, and read or stream the body
+async fn send_attempt(
+    http_client: &HttpClient,
+    method: &wreq::Method,
+    options: RequestOptions,
+    proxy: Option<&PickedProxy>,
+    total: Option<Deadline>,
+) -> Result<Response> {
+    let client = &http_client.client;
+
+    // Redirects may turn it into a GET
+    let mut http_method = method.clone();
+
+    let response_header_timeout = options
+        .response_header_timeout
+        .or(http_client.response_header_timeout);
+    let body_timeouts = BodyTimeouts {
+        read_ms: options.read_timeout.or(http_client.read_timeout),
+        total,
+    };
+
+    let mut current_url = options.url.clone();
+    let mut headers = options.headers.clone();
+    let mut body = options.body;
+    let mut content_length = options.content_length;
+    let mut redirects = Vec::new();
+
+    // Proxy of the last hop, reported with the response
+    let mut proxy_url: Option<String>;
+
+    let cookie_jar = options
+        .cookie_jar
+        .clone()
+        .unwrap_or_else(|| http_client.cookie_jar.clone());
+
+    // Redirects are followed here rather than inside wreq so every hop can be reported
+    let (response, cookies) = loop {
+        let hop_url =
+            Url::parse(&current_url).with_context(|| format!("Invalid URL: {}", current_url))?;
+
+        // Build request
+        let mut request = client.request(http_method.clone(), hop_url.as_str());
+
+        // Every hop goes through the proxy picked for the attempt, or the
+        // client's proxy for the hop's scheme and host
+        let hop_proxy = proxy.or_else(|| {
+            http_client
+                .proxy
+                .as_ref()
+                .and_then(|config| config.route(&hop_url))
+        });
+        if let Some(hop_proxy) = hop_proxy {
+            request = request.proxy(hop_proxy.proxy.clone());
+        }
+        proxy_url = hop_proxy.map(|hop_proxy| hop_proxy.url.clone());
+
+        // Changed profile headers are sent by us instead of the client's defaults
+        let hop_headers = if options.profile_headers.is_empty() {
+            headers.clone()
+        } else {
+            request = request.default_headers(false);
+            let profile = options.profile_headers.list(&http_client.profile_headers);
+            with_profile(profile, &headers, options.header_order)
+        };
+
+        // Apply custom headers in order (a Cookie header is merged with the jar's cookies)
+        let hop_headers = with_cookies(&hop_headers, cookie_jar.header_for(&hop_url));
+        for (key, value) in &hop_headers {
+            request = request.header(key, value);
+        }
+
+        if options.header_order == HeaderOrder::Request {
+            request = request.orig_headers(orig_header_map(&hop_headers));
+        }
+
+        // Apply body if present (buffered and file bodies can be replayed on 307/308)
+        let mut hop_length = content_length;
+        if let Some(body) = &body {
+            request = match body {
+                RequestBody::Bytes(bytes) => request.body(bytes.clone()),
+                RequestBody::File(path) => {
+                    let (body, length) = file_body(path).await?;
+                    hop_length = hop_length.or(Some(length));
+                    request.body(body)
+                }
+                RequestBody::Stream(stream) => request.body(stream.take_body()?),
+            };
+        }
+
+        // Declare the length up front instead of using chunked transfer encoding
+        if let Some(length) = hop_length {
+            request = request.header(wreq::header::CONTENT_LENGTH, length);
+        }
+
+        // Execute request, headers have to arrive before either deadline
+        let header_deadline = Deadline::earliest(
+            Deadline::after(response_header_timeout, TimeoutPhase::ResponseHeader),
+            body_timeouts.total,
+        );
+        let response = run_until(header_deadline, async {
+            request
+                .send()
+                .await
+                .with_context(|| format!("{} {}", http_method, current_url))
+        })
+        .await?;
+
+        // Every hop may set cookies, later hops already send them
+        let cookies = parse_set_cookies(response.headers());
+        cookie_jar.store_response(&hop_url, &cookies);
+
+        if !response.status().is_redirection() || options.redirect == RedirectMode::Manual {
+            break (response, cookies);
+        }
+
+        let location = match response
+            .headers()
+            .get(wreq::header::LOCATION)
+            .and_then(|value| value.to_str().ok())
+        {
+            Some(location) => location,
+            None => break (response, cookies),
+        };
 
-    // Apply timeout
-    request = request.timeout(Duration::from_millis(options.timeout));
+        let next = hop_url
+            .join(location)
+            .with_context(|| format!("Invalid redirect location: {}", location))?;
 
-    // Execute request
-    let response = request
-        .send()
-        .await
-        .with_context(|| format!("{} {}", method, options.url))?;
+        if options.redirect == RedirectMode::Error {
+            return Err(Error::new(
+                ErrorKind::Redirect,
+                format!(
+                    "Redirect from {} to {} is not allowed (redirect: 'error')",
+                    current_url, next
+                ),
+            )
+            .into());
+        }
+
+        if redirects.len() >= options.max_redirects {
+            return Err(Error::new(
+                ErrorKind::Redirect,
+                format!(
+                    "Too many redirects (maxRedirects: {})",
+                    options.max_redirects
+                ),
+            )
+            .into());
+        }
+
+        let status = response.status().as_u16();
+        let set_cookies = response
+            .headers()
+            .get_all(wreq::header::SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .map(|value| value.to_string())
+            .collect();
+
+        redirects.push(RedirectHop {
+            url: current_url.clone(),
+            status,
+            location: next.to_string(),
+            set_cookies,
+        });
+
+        // 303 (and 301/302 after a POST) continue as a GET without body, like browsers
+        let switch_to_get = match status {
+            303 => http_method != wreq::Method::HEAD,
+            301 | 302 => http_method == wreq::Method::POST,
+            _ => false,
+        };
+
+        if switch_to_get {
+            http_method = wreq::Method::GET;
+            body = None;
+            content_length = None;
+            headers.retain(|(name, _)| !is_body_header(name));
+        } else if matches!(body, Some(RequestBody::Stream(_))) {
+            return Err(Error::new(
+                ErrorKind::Redirect,
+                format!(
+                    "Cannot follow {} redirect to {}: a streamed request body can't be sent twice",
+                    status, next
+                ),
+            )
+            .into());
+        }
+
+        // Credentials are not forwarded to a different origin
+        if hop_url.origin() != next.origin() {
+            headers.retain(|(name, _)| !is_sensitive_header(name));
+        }
+
+        current_url = next.to_string();
+    };
 
     // Extract response data
     let status = response.status().as_u16();
@@ -105,37 +746,35 @@ pub async fn make_request(options: RequestOptions) -> Result<Response> {
     }
 }
 
-    // Extract cookies
-    let mut cookies = HashMap::new();
-    // if let Some(cookie_header) = response.headers().get("set-cookie") {
-    //     if let Ok(cookie_str) = cookie_header.to_str() {
-    //         // Simple cookie parsing (name=value)
-    //         for cookie_part in cookie_str.split(';') {
-    //             if let Some((key, value)) = cookie_part.trim().split_once('=') {
-    //                 cookies.insert(key.to_string(), value.to_string());
-    //             }
-    //         }
-    //     }
-    // }
+    // Hand the body over to JS chunk by chunk
+    if options.stream {
+        let stream_id = store_stream(BodyStream::new(
+            response,
+            options.abort.clone(),
+            body_timeouts,
+        ));
 
-    for cookie_header in response.headers().get_all("set-cookie") {
-        if let Ok(cookie_str) = cookie_header.to_str() {
-            // 解析单个Set-Cookie头：第一个部分是name=value，后续是属性
-            let parts: Vec<&str> = cookie_str.split(';').map(|s| s.trim()).collect();
-            if !parts.is_empty() {
-                if let Some((key, value)) = parts[0].split_once('=') {
-                    // 只插入name=value，忽略属性
-                    cookies.insert(key.to_string(), value.to_string());
-                }
-            }
-        }
+        return Ok(Response {
+            status,
+            headers: response_headers,
+            body: Vec::new(),
+            cookies,
+            url: final_url,
+            redirects,
+            stream_id: Some(stream_id),
+            attempts: 1,
+            profile: http_client.profile.clone(),
+            proxy: proxy_url,
+        });
     }
 
-    // Get body
-    let body = response
-        .text()
-        .await
-        .context("Failed to read response body")?;
+    // Get body as raw bytes (decoding is left to the caller), chunk by chunk so
+    // the read timeout applies between chunks
+    let body_stream = BodyStream::new(response, None, body_timeouts);
+    let mut body = Vec::new();
+    while let Some(chunk) = body_stream.next_chunk().await? {
+        body.extend_from_slice(&chunk);
+    }
 
     Ok(Response {
         status,
@@ -143,5 +782,62 @@ pub async fn make_request(options: RequestOptions) -> Result<Response> {
         body,
         cookies,
         url: final_url,
+        redirects,
+        stream_id: None,
+        attempts: 1,
+        profile: http_client.profile.clone(),
+        proxy: proxy_url,
     })
 }
+
+// Headers describing the body, dropped when a redirect turns the request into a GET
+fn is_body_header(name: &str) -> bool {
+    name.eq_ignore_ascii_case("content-type")
+        || name.eq_ignore_ascii_case("content-length")
+        || name.eq_ignore_ascii_case("content-encoding")
+        || name.eq_ignore_ascii_case("transfer-encoding")
+}
+
+// Credential headers, dropped when a redirect leaves the original origin
+fn is_sensitive_header(name: &str) -> bool {
+    name.eq_ignore_ascii_case("authorization")
+        || name.eq_ignore_ascii_case("cookie")
+        || name.eq_ignore_ascii_case("proxy-authorization")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+
+    #[test]
+    fn retry_after_reads_seconds_and_dates() {
+        assert_eq!(retry_after(" 120 "), Some(Duration::from_secs(120)));
+        assert_eq!(
+            retry_after(&httpdate::fmt_http_date(SystemTime::UNIX_EPOCH)),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(retry_after("-5"), None);
+        assert_eq!(retry_after("soon"), None);
+
+        let later = httpdate::fmt_http_date(SystemTime::now() + Duration::from_secs(60));
+        let delay = retry_after(&later).unwrap();
+        assert!(delay > Duration::from_secs(55) && delay <= Duration::from_secs(60));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        let policy = RetryPolicy {
+            base_delay_ms: 100,
+            max_delay_ms: 500,
+            jitter: false,
+            ..RetryPolicy::default()
+        };
+
+        let delays: Vec<u128> = [1, 2, 3, 4, u32::MAX]
+            .iter()
+            .map(|&attempt| policy.backoff(attempt).as_millis())
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 500, 500]);
+    }
+}
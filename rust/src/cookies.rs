@@ -0,0 +1,519 @@
+use anyhow::{anyhow, Context, Result};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use url::Url;
+
+// Global storage for cookie jars
+static COOKIE_JARS: Lazy<StdMutex<HashMap<u64, Arc<CookieJar>>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+static NEXT_JAR_ID: Lazy<StdMutex<u64>> = Lazy::new(|| StdMutex::new(1));
+
+/// Cookie from a `Set-Cookie` header with all of its attributes
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    /// `Domain` attribute without its leading dot
+    pub domain: Option<String>,
+    pub path: Option<String>,
+    /// `Expires` attribute as a Unix timestamp in seconds
+    pub expires: Option<i64>,
+    /// `Max-Age` attribute in seconds
+    pub max_age: Option<i64>,
+    pub secure: bool,
+    pub http_only: bool,
+    /// `SameSite` attribute: "Strict", "Lax" or "None"
+    pub same_site: Option<String>,
+    /// Header value as received
+    pub raw: String,
+}
+
+impl Cookie {
+    /// Parse a single `Set-Cookie` header value, `None` if it has no name=value pair
+    pub fn parse(raw: &str) -> Option<Self> {
+        let parsed = cookie::Cookie::parse(raw).ok()?;
+
+        Some(Self {
+            name: parsed.name().to_string(),
+            value: parsed.value().to_string(),
+            domain: parsed
+                .domain()
+                .filter(|domain| !domain.is_empty())
+                .map(|domain| domain.to_string()),
+            path: parsed.path().map(|path| path.to_string()),
+            expires: parsed
+                .expires_datetime()
+                .map(|datetime| datetime.unix_timestamp()),
+            max_age: parsed.max_age().map(|max_age| max_age.whole_seconds()),
+            secure: parsed.secure().unwrap_or(false),
+            http_only: parsed.http_only().unwrap_or(false),
+            same_site: parsed.same_site().map(|same_site| same_site.to_string()),
+            raw: raw.to_string(),
+        })
+    }
+}
+
+/// Parse every `Set-Cookie` header of a response, in order
+pub fn parse_set_cookies(headers: &wreq::header::HeaderMap) -> Vec<Cookie> {
+    headers
+        .get_all(wreq::header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(Cookie::parse)
+        .collect()
+}
+
+/// Cookie as kept by a `CookieJar`, with domain, path and expiry resolved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredCookie {
+    pub name: String,
+    pub value: String,
+    /// Lowercase domain without leading dot
+    pub domain: String,
+    /// Only sent to `domain` itself, not its subdomains
+    #[serde(default)]
+    pub host_only: bool,
+    pub path: String,
+    /// Unix timestamp in seconds, `None` for session cookies
+    #[serde(default)]
+    pub expires: Option<i64>,
+    #[serde(default)]
+    pub secure: bool,
+    #[serde(default)]
+    pub http_only: bool,
+    #[serde(default)]
+    pub same_site: Option<String>,
+}
+
+impl StoredCookie {
+    fn is_expired(&self, now: i64) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn same_key(&self, other: &StoredCookie) -> bool {
+        self.name == other.name && self.domain == other.domain && self.path == other.path
+    }
+
+    fn matches(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+
+        let domain_ok = if self.host_only {
+            host == self.domain
+        } else {
+            domain_match(&host, &self.domain)
+        };
+
+        let secure_ok = !self.secure || matches!(url.scheme(), "https" | "wss");
+
+        domain_ok && secure_ok && path_match(url.path(), &self.path)
+    }
+}
+
+/// Cookie store shared between requests and WebSocket connects.
+///
+/// Follows the RFC 6265 storage model: cookies are keyed by name, domain and
+/// path, and only sent to URLs whose host, path and scheme they match.
+#[derive(Debug, Default)]
+pub struct CookieJar {
+    cookies: StdMutex<Vec<StoredCookie>>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Store the cookies a response from `url` set
+    pub fn store_response(&self, url: &Url, cookies: &[Cookie]) {
+        for cookie in cookies {
+            self.store(url, cookie);
+        }
+    }
+
+    /// Store a single cookie received from `url`, `false` if it was rejected
+    pub fn store(&self, url: &Url, cookie: &Cookie) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+        let host = host.to_ascii_lowercase();
+
+        // A Domain attribute must cover the host that set it, and can't be a public
+        // suffix like `com` or `co.uk` unless that is the host itself (RFC 6265 5.3)
+        let (domain, host_only) = match &cookie.domain {
+            Some(domain) => {
+                let domain = domain.trim_start_matches('.').to_ascii_lowercase();
+                if !domain_match(&host, &domain) {
+                    return false;
+                }
+                if !is_public_suffix(&domain) {
+                    (domain, false)
+                } else if domain == host {
+                    (host, true)
+                } else {
+                    return false;
+                }
+            }
+            None => (host, true),
+        };
+
+        let path = match &cookie.path {
+            Some(path) if path.starts_with('/') => path.clone(),
+            _ => default_path(url.path()),
+        };
+
+        // Max-Age wins over Expires
+        let now = unix_now();
+        let expires = match cookie.max_age {
+            Some(max_age) => Some(now.saturating_add(max_age)),
+            None => cookie.expires,
+        };
+
+        self.insert(StoredCookie {
+            name: cookie.name.clone(),
+            value: cookie.value.clone(),
+            domain,
+            host_only,
+            path,
+            expires,
+            secure: cookie.secure,
+            http_only: cookie.http_only,
+            same_site: cookie.same_site.clone(),
+        });
+        true
+    }
+
+    /// Add or replace a cookie; an already expired one deletes its key instead
+    pub fn insert(&self, cookie: StoredCookie) {
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|existing| !existing.same_key(&cookie));
+
+        if !cookie.is_expired(unix_now()) {
+            cookies.push(cookie);
+        }
+    }
+
+    /// Cookies to send to `url`, longest path first
+    pub fn cookies_for(&self, url: &Url) -> Vec<StoredCookie> {
+        let now = unix_now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| !cookie.is_expired(now));
+
+        let mut matching: Vec<StoredCookie> = cookies
+            .iter()
+            .filter(|cookie| cookie.matches(url))
+            .cloned()
+            .collect();
+
+        // Stable sort keeps creation order for equal path lengths
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()));
+        matching
+    }
+
+    /// `Cookie` header value for `url`, `None` when no cookie matches
+    pub fn header_for(&self, url: &Url) -> Option<String> {
+        let cookies = self.cookies_for(url);
+        if cookies.is_empty() {
+            return None;
+        }
+
+        Some(
+            cookies
+                .iter()
+                .map(|cookie| format!("{}={}", cookie.name, cookie.value))
+                .collect::<Vec<_>>()
+                .join("; "),
+        )
+    }
+
+    /// Every unexpired cookie in the jar
+    pub fn all(&self) -> Vec<StoredCookie> {
+        let now = unix_now();
+        let mut cookies = self.cookies.lock().unwrap();
+        cookies.retain(|cookie| !cookie.is_expired(now));
+        cookies.clone()
+    }
+
+    /// Remove cookies by name, optionally limited to a domain and path; returns how many
+    pub fn delete(&self, name: &str, domain: Option<&str>, path: Option<&str>) -> usize {
+        let domain = domain.map(|domain| domain.trim_start_matches('.').to_ascii_lowercase());
+        let mut cookies = self.cookies.lock().unwrap();
+        let before = cookies.len();
+
+        cookies.retain(|cookie| {
+            let matches = cookie.name == name
+                && domain
+                    .as_ref()
+                    .is_none_or(|domain| &cookie.domain == domain)
+                && path.is_none_or(|path| cookie.path == path);
+            !matches
+        });
+
+        before - cookies.len()
+    }
+
+    pub fn clear(&self) {
+        self.cookies.lock().unwrap().clear();
+    }
+
+    /// Serialize every cookie as a JSON array
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string(&self.all()).context("Failed to serialize cookies")
+    }
+
+    /// Add the cookies of a JSON array produced by `to_json`
+    pub fn load_json(&self, json: &str) -> Result<()> {
+        let cookies: Vec<StoredCookie> =
+            serde_json::from_str(json).context("Failed to parse cookie JSON")?;
+
+        for mut cookie in cookies {
+            cookie.domain = cookie.domain.trim_start_matches('.').to_ascii_lowercase();
+            self.insert(cookie);
+        }
+        Ok(())
+    }
+
+    /// Serialize in the Netscape `cookies.txt` format used by curl and browsers
+    pub fn to_netscape(&self) -> String {
+        let mut output = String::from("# Netscape HTTP Cookie File\n");
+
+        for cookie in self.all() {
+            let domain = if cookie.host_only {
+                cookie.domain.clone()
+            } else {
+                format!(".{}", cookie.domain)
+            };
+
+            output.push_str(&format!(
+                "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.http_only { "#HttpOnly_" } else { "" },
+                domain,
+                netscape_bool(!cookie.host_only),
+                cookie.path,
+                netscape_bool(cookie.secure),
+                cookie.expires.unwrap_or(0),
+                cookie.name,
+                cookie.value,
+            ));
+        }
+
+        output
+    }
+
+    /// Add the cookies of a Netscape `cookies.txt` file
+    pub fn load_netscape(&self, text: &str) -> Result<()> {
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim_end_matches('\r');
+
+            let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                Some(rest) => (rest, true),
+                None => (line, false),
+            };
+
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 7 {
+                return Err(anyhow!(
+                    "Invalid cookies.txt line {}: expected 7 tab separated fields, got {}",
+                    index + 1,
+                    fields.len()
+                ));
+            }
+
+            let expires: i64 = fields[4]
+                .trim()
+                .parse()
+                .with_context(|| format!("Invalid cookies.txt line {}: bad expiry", index + 1))?;
+
+            self.insert(StoredCookie {
+                name: fields[5].to_string(),
+                value: fields[6].to_string(),
+                domain: fields[0].trim_start_matches('.').to_ascii_lowercase(),
+                host_only: !fields[1].eq_ignore_ascii_case("TRUE"),
+                path: fields[2].to_string(),
+                expires: (expires != 0).then_some(expires),
+                secure: fields[3].eq_ignore_ascii_case("TRUE"),
+                http_only,
+                same_site: None,
+            });
+        }
+
+        Ok(())
+    }
+}
+
+fn netscape_bool(value: bool) -> &'static str {
+    if value {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+// RFC 6265 5.1.3: the host is the domain or one of its subdomains
+fn domain_match(host: &str, domain: &str) -> bool {
+    host == domain
+        || (host.ends_with(domain)
+            && host[..host.len() - domain.len()].ends_with('.')
+            && host.parse::<IpAddr>().is_err())
+}
+
+// Suffixes on the Public Suffix List; unlisted TLDs (intranet names) are not treated as one
+fn is_public_suffix(domain: &str) -> bool {
+    psl::suffix(domain.as_bytes())
+        .is_some_and(|suffix| suffix.is_known() && suffix.as_bytes() == domain.as_bytes())
+}
+
+// RFC 6265 5.1.4: the cookie path is a prefix of the request path at a `/` boundary
+fn path_match(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+// RFC 6265 5.1.4: directory of the request path
+fn default_path(request_path: &str) -> String {
+    match request_path.rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => request_path[..index].to_string(),
+    }
+}
+
+/// Store a cookie jar and return its ID
+pub fn store_jar(jar: Arc<CookieJar>) -> u64 {
+    let mut id_lock = NEXT_JAR_ID.lock().unwrap();
+    let id = *id_lock;
+    *id_lock += 1;
+    drop(id_lock);
+
+    let mut jars = COOKIE_JARS.lock().unwrap();
+    jars.insert(id, jar);
+    id
+}
+
+/// Get a cookie jar by ID
+pub fn get_jar(id: u64) -> Option<Arc<CookieJar>> {
+    let jars = COOKIE_JARS.lock().unwrap();
+    jars.get(&id).cloned()
+}
+
+/// Remove a cookie jar (requests and clients using it keep their reference)
+pub fn remove_jar(id: u64) {
+    let mut jars = COOKIE_JARS.lock().unwrap();
+    jars.remove(&id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_reads_attributes_and_needs_a_pair() {
+        let cookie = Cookie::parse(concat!(
+            "id=a3fWa; Domain=.Example.com; Path=/docs; Expires=Wed, 21 Oct 2015 07:28:00 GMT; ",
+            "Max-Age=3600; Secure; HttpOnly; SameSite=Lax"
+        ))
+        .unwrap();
+
+        assert_eq!(
+            (cookie.name.as_str(), cookie.value.as_str()),
+            ("id", "a3fWa")
+        );
+        assert_eq!(cookie.domain.as_deref(), Some("Example.com"));
+        assert_eq!(cookie.path.as_deref(), Some("/docs"));
+        assert_eq!(cookie.expires, Some(1_445_412_480));
+        assert_eq!(cookie.max_age, Some(3600));
+        assert!(cookie.secure && cookie.http_only);
+        assert_eq!(cookie.same_site.as_deref(), Some("Lax"));
+
+        assert_eq!(Cookie::parse("no-pair"), None);
+    }
+
+    #[test]
+    fn jar_scopes_cookies_by_domain_path_and_scheme() {
+        let jar = CookieJar::new();
+        let origin = Url::parse("https://www.example.com/account/login").unwrap();
+        for raw in [
+            "host=1",
+            "shared=1; Domain=example.com; Path=/",
+            "secure=1; Secure; Path=/",
+        ] {
+            assert!(jar.store(&origin, &Cookie::parse(raw).unwrap()));
+        }
+        // The Domain attribute has to cover the host setting it, and not be a public suffix
+        for raw in ["a=1; Domain=api.example.com", "a=1; Domain=com"] {
+            assert!(!jar.store(&origin, &Cookie::parse(raw).unwrap()));
+        }
+
+        let header = |url: &str| jar.header_for(&Url::parse(url).unwrap());
+        assert_eq!(
+            header("https://www.example.com/account/x").as_deref(),
+            Some("host=1; shared=1; secure=1")
+        );
+        assert_eq!(
+            header("http://www.example.com/account/x").as_deref(),
+            Some("host=1; shared=1")
+        );
+        assert_eq!(
+            header("https://api.example.com/").as_deref(),
+            Some("shared=1")
+        );
+        assert_eq!(
+            header("https://www.example.com/accounts").as_deref(),
+            Some("shared=1; secure=1")
+        );
+        assert_eq!(header("https://example.org/"), None);
+
+        jar.store(
+            &origin,
+            &Cookie::parse("host=2; Path=/account; Max-Age=0").unwrap(),
+        );
+        assert_eq!(
+            header("https://www.example.com/account/x").as_deref(),
+            Some("shared=1; secure=1")
+        );
+    }
+
+    #[test]
+    fn jar_round_trips_through_cookies_txt_and_json() {
+        let jar = CookieJar::new();
+        let origin = Url::parse("https://www.example.com/").unwrap();
+        jar.store(&origin, &Cookie::parse("session=abc; HttpOnly").unwrap());
+        jar.store(
+            &origin,
+            &Cookie::parse("pref=dark; Domain=example.com; Path=/app; Secure; Max-Age=3600")
+                .unwrap(),
+        );
+
+        let text = jar.to_netscape();
+        assert!(text.contains("#HttpOnly_www.example.com\tFALSE\t/\tFALSE\t0\tsession\tabc\n"));
+        let loaded = CookieJar::new();
+        loaded.load_netscape(&text).unwrap();
+        assert_eq!(loaded.all(), jar.all());
+
+        let loaded = CookieJar::new();
+        loaded.load_json(&jar.to_json().unwrap()).unwrap();
+        assert_eq!(loaded.all(), jar.all());
+
+        assert!(loaded.load_netscape("example.com\tFALSE\t/").is_err());
+    }
+}
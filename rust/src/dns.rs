@@ -0,0 +1,486 @@
+use anyhow::{anyhow, Context, Result};
+use hickory_resolver::config::{NameServerConfigGroup, ResolverConfig};
+use hickory_resolver::name_server::TokioConnectionProvider;
+use hickory_resolver::TokioResolver;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::{Duration, Instant};
+use url::Url;
+use wreq::dns::{Addrs, Name, Resolve, Resolving};
+
+use crate::error::invalid;
+use crate::proxy::ProxyConfig;
+
+/// DNS-over-HTTPS endpoint used when none is given (JSON API)
+pub const DEFAULT_DOH_URL: &str = "https://cloudflare-dns.com/dns-query";
+
+// Cached hosts before expired answers are swept out
+const MAX_CACHE_ENTRIES: usize = 1024;
+
+// Time a DNS-over-HTTPS query may take, so a blackholed server fails the lookup
+// instead of holding the connection until the request's own deadline
+const DOH_QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Order resolved addresses are tried in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DnsOrder {
+    /// As returned by the resolver
+    #[default]
+    Verbatim,
+    /// IPv4 addresses first, IPv6 as fallback
+    Ipv4First,
+    /// IPv6 addresses first, IPv4 as fallback
+    Ipv6First,
+}
+
+impl DnsOrder {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "verbatim" => Some(Self::Verbatim),
+            "ipv4first" => Some(Self::Ipv4First),
+            "ipv6first" => Some(Self::Ipv6First),
+            _ => None,
+        }
+    }
+
+    /// Sort addresses so the preferred family comes first, keeping their order otherwise
+    pub fn sort(self, addrs: &mut [SocketAddr]) {
+        match self {
+            Self::Verbatim => {}
+            Self::Ipv4First => addrs.sort_by_key(|addr| addr.is_ipv6()),
+            Self::Ipv6First => addrs.sort_by_key(|addr| addr.is_ipv4()),
+        }
+    }
+}
+
+/// Where host names are looked up
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum DnsBackend {
+    /// The operating system's resolver (`getaddrinfo`)
+    #[default]
+    System,
+    /// hickory-dns, with the system configuration when no nameservers are given
+    Hickory { nameservers: Vec<SocketAddr> },
+    /// DNS-over-HTTPS JSON API, queried with the request's browser profile
+    Doh { url: Url },
+}
+
+/// How the client resolves host names
+#[derive(Debug, Clone, Default)]
+pub struct DnsOptions {
+    pub backend: DnsBackend,
+    /// Fixed addresses per host, used instead of a lookup (like curl `--resolve`)
+    pub overrides: HashMap<String, Vec<IpAddr>>,
+    /// Upper bound for keeping answers in the client's cache, `None` without a cache
+    pub cache_ttl: Option<Duration>,
+    pub order: DnsOrder,
+}
+
+impl DnsOptions {
+    /// Whether wreq's own resolver does the same job
+    pub fn is_default(&self) -> bool {
+        self.backend == DnsBackend::System
+            && self.overrides.is_empty()
+            && self.cache_ttl.is_none()
+            && self.order == DnsOrder::Verbatim
+    }
+
+    /// Add a `resolve` entry for a host; DNS has no notion of ports, so keys with
+    /// one are rejected rather than applied to every port of the host
+    pub fn add_override(&mut self, key: &str, addresses: &[String]) -> Result<()> {
+        let host = override_host(key).ok_or_else(|| {
+            invalid(format!(
+                "Invalid host in resolve: {} (give the host without a port)",
+                key
+            ))
+        })?;
+
+        let addresses = addresses
+            .iter()
+            .map(|address| {
+                address
+                    .trim()
+                    .trim_start_matches('[')
+                    .trim_end_matches(']')
+                    .parse::<IpAddr>()
+                    .map_err(|_| {
+                        invalid(format!(
+                            "Invalid address for {} in resolve: {}",
+                            key, address
+                        ))
+                    })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        if addresses.is_empty() {
+            return Err(invalid(format!("No address for {} in resolve", key)));
+        }
+
+        self.overrides.entry(host).or_default().extend(addresses);
+        Ok(())
+    }
+}
+
+// Host of a `resolve` key, lowercased and without IPv6 brackets; None if it has a port
+fn override_host(key: &str) -> Option<String> {
+    let key = key.trim().to_ascii_lowercase();
+
+    let host = match key.strip_prefix('[') {
+        Some(rest) => rest.strip_suffix(']')?,
+        None if key.parse::<IpAddr>().is_ok() => key.as_str(),
+        None if key.contains(':') => return None,
+        None => key.as_str(),
+    };
+
+    (!host.is_empty()).then(|| host.to_string())
+}
+
+/// Parse a nameserver: an IP address, optionally with a port (`[v6]:port` for IPv6)
+pub fn parse_nameserver(nameserver: &str) -> Option<SocketAddr> {
+    let nameserver = nameserver.trim();
+
+    nameserver.parse::<SocketAddr>().ok().or_else(|| {
+        let ip = nameserver.trim_start_matches('[').trim_end_matches(']');
+        ip.parse::<IpAddr>().ok().map(|ip| SocketAddr::new(ip, 53))
+    })
+}
+
+/// Resolver installed on clients with custom DNS settings
+#[derive(Clone)]
+pub struct DnsResolver {
+    inner: Arc<ResolverInner>,
+}
+
+struct ResolverInner {
+    backend: Backend,
+    overrides: HashMap<String, Vec<IpAddr>>,
+    cache: Option<DnsCache>,
+    order: DnsOrder,
+}
+
+enum Backend {
+    System,
+    Hickory(TokioResolver),
+    Doh(DohClient),
+}
+
+impl DnsResolver {
+    /// Build the resolver; `doh_client` creates the emulated client DoH
+    /// queries are sent with, routed through `proxy` like other requests
+    pub fn new(
+        options: &DnsOptions,
+        doh_client: impl FnOnce() -> Result<wreq::Client>,
+        proxy: Option<&ProxyConfig>,
+    ) -> Result<Self> {
+        let backend = match &options.backend {
+            DnsBackend::System => Backend::System,
+            DnsBackend::Hickory { nameservers } => Backend::Hickory(hickory_resolver(nameservers)?),
+            DnsBackend::Doh { url } => Backend::Doh(DohClient {
+                client: doh_client()?,
+                url: url.clone(),
+                proxy: proxy
+                    .and_then(|config| config.route(url))
+                    .map(|picked| picked.proxy.clone()),
+            }),
+        };
+
+        Ok(Self {
+            inner: Arc::new(ResolverInner {
+                backend,
+                overrides: options.overrides.clone(),
+                cache: options.cache_ttl.map(DnsCache::new),
+                order: options.order,
+            }),
+        })
+    }
+}
+
+impl ResolverInner {
+    async fn lookup(&self, host: &str) -> Result<Vec<IpAddr>> {
+        if let Some(addresses) = self.overrides.get(host) {
+            return Ok(addresses.clone());
+        }
+
+        if let Some(addresses) = self.cache.as_ref().and_then(|cache| cache.get(host)) {
+            return Ok(addresses);
+        }
+
+        let (addresses, ttl) = match &self.backend {
+            Backend::System => {
+                let addresses = tokio::net::lookup_host((host, 0))
+                    .await?
+                    .map(|addr| addr.ip())
+                    .collect();
+                (addresses, None)
+            }
+            Backend::Hickory(resolver) => {
+                let lookup = resolver.lookup_ip(host).await?;
+                let ttl = lookup
+                    .valid_until()
+                    .saturating_duration_since(Instant::now());
+                (lookup.iter().collect(), Some(ttl))
+            }
+            Backend::Doh(doh) => doh.lookup(host).await?,
+        };
+
+        if let Some(cache) = &self.cache {
+            cache.insert(host, &addresses, ttl);
+        }
+
+        Ok(addresses)
+    }
+}
+
+impl Resolve for DnsResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let inner = self.inner.clone();
+
+        Box::pin(async move {
+            let host = name.as_str().to_ascii_lowercase();
+            let addresses = inner.lookup(&host).await.map_err(|e| {
+                let error: Box<dyn std::error::Error + Send + Sync> = e.into();
+                error
+            })?;
+
+            let mut addrs: Vec<SocketAddr> = addresses
+                .into_iter()
+                .map(|ip| SocketAddr::new(ip, 0))
+                .collect();
+            inner.order.sort(&mut addrs);
+
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+fn hickory_resolver(nameservers: &[SocketAddr]) -> Result<TokioResolver> {
+    let provider = TokioConnectionProvider::default();
+
+    if nameservers.is_empty() {
+        let builder = TokioResolver::builder(provider)
+            .context("Failed to read the system DNS configuration")?;
+        return Ok(builder.build());
+    }
+
+    let mut group = NameServerConfigGroup::new();
+    for nameserver in nameservers {
+        group.merge(NameServerConfigGroup::from_ips_clear(
+            &[nameserver.ip()],
+            nameserver.port(),
+            true,
+        ));
+    }
+
+    let config = ResolverConfig::from_parts(None, Vec::new(), group);
+    Ok(TokioResolver::builder_with_config(config, provider).build())
+}
+
+/// Answers kept for the lifetime of a client
+struct DnsCache {
+    max_ttl: Duration,
+    entries: StdMutex<HashMap<String, (Vec<IpAddr>, Instant)>>,
+}
+
+impl DnsCache {
+    fn new(max_ttl: Duration) -> Self {
+        Self {
+            max_ttl,
+            entries: StdMutex::new(HashMap::new()),
+        }
+    }
+
+    fn get(&self, host: &str) -> Option<Vec<IpAddr>> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .get(host)
+            .filter(|(_, expires)| *expires > Instant::now())
+            .map(|(addresses, _)| addresses.clone())
+    }
+
+    // Keep an answer for its TTL, capped at `max_ttl` (which applies alone
+    // when the resolver doesn't report one)
+    fn insert(&self, host: &str, addresses: &[IpAddr], ttl: Option<Duration>) {
+        let ttl = ttl.map_or(self.max_ttl, |ttl| ttl.min(self.max_ttl));
+        if ttl.is_zero() || addresses.is_empty() {
+            return;
+        }
+
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() >= MAX_CACHE_ENTRIES {
+            entries.retain(|_, (_, expires)| *expires > now);
+        }
+        entries.insert(host.to_string(), (addresses.to_vec(), now + ttl));
+    }
+}
+
+/// DNS-over-HTTPS client speaking the JSON API (Cloudflare, Google, ...)
+struct DohClient {
+    client: wreq::Client,
+    url: Url,
+    proxy: Option<wreq::Proxy>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DohResponse {
+    status: u32,
+    #[serde(default)]
+    answer: Vec<DohAnswer>,
+}
+
+#[derive(Deserialize)]
+struct DohAnswer {
+    #[serde(rename = "type")]
+    record_type: u16,
+    #[serde(rename = "TTL")]
+    ttl: u64,
+    data: String,
+}
+
+impl DohClient {
+    /// A and AAAA records of `host`, with the lowest TTL among them
+    async fn lookup(&self, host: &str) -> Result<(Vec<IpAddr>, Option<Duration>)> {
+        let (ipv4, ipv6) =
+            futures_util::future::join(self.query(host, "A", 1), self.query(host, "AAAA", 28))
+                .await;
+
+        let (mut addresses, mut ttl) = (Vec::new(), None::<u64>);
+        let mut error = None;
+        for answer in [ipv4, ipv6] {
+            match answer {
+                Ok((found, found_ttl)) => {
+                    addresses.extend(found);
+                    ttl = match (ttl, found_ttl) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    };
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+
+        match error {
+            Some(error) if addresses.is_empty() => Err(error),
+            _ => Ok((addresses, ttl.map(Duration::from_secs))),
+        }
+    }
+
+    async fn query(
+        &self,
+        host: &str,
+        record_name: &str,
+        record_type: u16,
+    ) -> Result<(Vec<IpAddr>, Option<u64>)> {
+        let mut url = self.url.clone();
+        url.query_pairs_mut()
+            .append_pair("name", host)
+            .append_pair("type", record_name);
+
+        let mut request = self
+            .client
+            .get(url.as_str())
+            .header(wreq::header::ACCEPT, "application/dns-json")
+            .timeout(DOH_QUERY_TIMEOUT);
+        if let Some(proxy) = &self.proxy {
+            request = request.proxy(proxy.clone());
+        }
+
+        let response = request
+            .send()
+            .await
+            .with_context(|| format!("DNS-over-HTTPS query to {} failed", self.url))?;
+        if !response.status().is_success() {
+            return Err(anyhow!(
+                "dns error: DNS-over-HTTPS server answered with status {}",
+                response.status()
+            ));
+        }
+
+        let body: DohResponse = response
+            .json()
+            .await
+            .with_context(|| format!("Invalid DNS-over-HTTPS answer from {}", self.url))?;
+        if body.status != 0 {
+            return Err(anyhow!(
+                "dns error: lookup failed with DNS status {}",
+                body.status
+            ));
+        }
+
+        let answers: Vec<&DohAnswer> = body
+            .answer
+            .iter()
+            .filter(|answer| answer.record_type == record_type)
+            .collect();
+
+        let addresses = answers
+            .iter()
+            .filter_map(|answer| answer.data.parse::<IpAddr>().ok())
+            .collect();
+        let ttl = answers.iter().map(|answer| answer.ttl).min();
+
+        Ok((addresses, ttl))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_moves_the_preferred_family_first() {
+        let mut addrs: Vec<SocketAddr> = ["[::1]:443", "10.0.0.1:443", "[::2]:443", "10.0.0.2:443"]
+            .iter()
+            .map(|addr| addr.parse().unwrap())
+            .collect();
+        DnsOrder::Ipv4First.sort(&mut addrs);
+
+        let ordered: Vec<String> = addrs.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            ordered,
+            ["10.0.0.1:443", "10.0.0.2:443", "[::1]:443", "[::2]:443"]
+        );
+    }
+
+    #[test]
+    fn overrides_and_nameservers_parse() {
+        let mut options = DnsOptions::default();
+        let addresses = ["10.0.0.1".to_string(), "[::1]".to_string()];
+        options.add_override("API.example.com", &addresses).unwrap();
+        assert_eq!(options.overrides["api.example.com"].len(), 2);
+        options.add_override("[::1]", &addresses).unwrap();
+        assert!(options.overrides.contains_key("::1"));
+        assert!(options.add_override("example.com:443", &addresses).is_err());
+        assert!(options.add_override("[::1]:443", &addresses).is_err());
+        assert!(options.add_override("example.com", &[]).is_err());
+        assert!(options
+            .add_override("example.com", &["not-an-ip".to_string()])
+            .is_err());
+
+        assert_eq!(parse_nameserver("1.1.1.1"), "1.1.1.1:53".parse().ok());
+        assert_eq!(
+            parse_nameserver("[2606:4700::1111]"),
+            "[2606:4700::1111]:53".parse().ok()
+        );
+        assert_eq!(parse_nameserver("[::1]:5353"), "[::1]:5353".parse().ok());
+        assert_eq!(parse_nameserver("dns.google"), None);
+    }
+
+    #[test]
+    fn cache_caps_ttls_at_its_limit() {
+        let cache = DnsCache::new(Duration::from_secs(60));
+        let addresses: [IpAddr; 1] = ["10.0.0.1".parse().unwrap()];
+        cache.insert("long.example", &addresses, Some(Duration::from_secs(3600)));
+        cache.insert("zero.example", &addresses, Some(Duration::ZERO));
+
+        assert_eq!(cache.get("long.example"), Some(addresses.to_vec()));
+        assert_eq!(cache.get("zero.example"), None);
+
+        let (_, expires) = cache.entries.lock().unwrap()["long.example"].clone();
+        assert!(expires <= Instant::now() + Duration::from_secs(60));
+    }
+}
@@ -0,0 +1,242 @@
+use std::io;
+use thiserror::Error as ThisError;
+
+/// Failure categories, surfaced to JS as the error `code`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ThisError)]
+pub enum ErrorKind {
+    #[error("request timed out")]
+    Timeout,
+    #[error("connection refused")]
+    ConnectionRefused,
+    #[error("connection reset")]
+    ConnectionReset,
+    #[error("DNS lookup failed")]
+    Dns,
+    #[error("TLS handshake failed")]
+    Tls,
+    #[error("proxy error")]
+    Proxy,
+    #[error("invalid URL")]
+    InvalidUrl,
+    #[error("invalid request")]
+    InvalidRequest,
+    #[error("redirect error")]
+    Redirect,
+    #[error("failed to read body")]
+    Body,
+    #[error("WebSocket error")]
+    WebSocket,
+    #[error("operation aborted")]
+    Aborted,
+    #[error("request failed")]
+    Other,
+}
+
+impl ErrorKind {
+    /// Stable code exposed as `error.code` (Node.js style where one exists)
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "ETIMEDOUT",
+            ErrorKind::ConnectionRefused => "ECONNREFUSED",
+            ErrorKind::ConnectionReset => "ECONNRESET",
+            ErrorKind::Dns => "ENOTFOUND",
+            ErrorKind::Tls => "ETLS",
+            ErrorKind::Proxy => "EPROXY",
+            ErrorKind::InvalidUrl => "EINVALIDURL",
+            ErrorKind::InvalidRequest => "EINVALIDREQUEST",
+            ErrorKind::Redirect => "EREDIRECT",
+            ErrorKind::Body => "EBODY",
+            ErrorKind::WebSocket => "EWEBSOCKET",
+            ErrorKind::Aborted => "ABORT_ERR",
+            ErrorKind::Other => "EREQUEST",
+        }
+    }
+}
+
+/// Stage of a request a timeout fired in, surfaced to JS as `error.phase`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeoutPhase {
+    /// TCP connect and TLS handshake (`connectTimeout`)
+    Connect,
+    /// Waiting for the response headers (`responseHeaderTimeout`)
+    ResponseHeader,
+    /// Idle between two body chunks (`readTimeout`)
+    Read,
+    /// The whole request (`totalTimeout` / `timeout`)
+    Total,
+}
+
+impl TimeoutPhase {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimeoutPhase::Connect => "connect",
+            TimeoutPhase::ResponseHeader => "responseHeader",
+            TimeoutPhase::Read => "read",
+            TimeoutPhase::Total => "total",
+        }
+    }
+}
+
+/// Error with its category and the request it belongs to
+#[derive(Debug, ThisError)]
+#[error("{message}")]
+pub struct Error {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub url: Option<String>,
+    pub method: Option<String>,
+    /// Innermost error of the chain, when it differs from the message
+    pub cause: Option<String>,
+    /// Set for `ErrorKind::Timeout`, when the phase is known
+    pub phase: Option<TimeoutPhase>,
+}
+
+impl Error {
+    pub fn new(kind: ErrorKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            url: None,
+            method: None,
+            cause: None,
+            phase: None,
+        }
+    }
+
+    pub fn timeout(phase: TimeoutPhase, message: impl Into<String>) -> Self {
+        Self {
+            phase: Some(phase),
+            ..Self::new(ErrorKind::Timeout, message)
+        }
+    }
+
+    /// Build from an error chain, keeping the kind of an `Error` raised further down
+    pub fn from_anyhow(err: &anyhow::Error) -> Self {
+        let root = err.root_cause().to_string();
+        let message = format!("{:#}", err);
+
+        let kind = classify(err);
+        let phase = match kind {
+            ErrorKind::Timeout => timeout_phase(err),
+            _ => None,
+        };
+
+        Self {
+            kind,
+            cause: (root != message).then_some(root),
+            message,
+            url: None,
+            method: None,
+            phase,
+        }
+    }
+
+    /// Use `kind` when the chain didn't match any more specific category
+    pub fn or_kind(mut self, kind: ErrorKind) -> Self {
+        if self.kind == ErrorKind::Other {
+            self.kind = kind;
+        }
+        self
+    }
+
+    /// Attach the request the error belongs to
+    pub fn with_request(mut self, method: impl Into<String>, url: impl Into<String>) -> Self {
+        self.method = Some(method.into());
+        self.url = Some(url.into());
+        self
+    }
+}
+
+/// An `ErrorKind::InvalidRequest` error for a rejected option or argument
+pub fn invalid(message: impl Into<String>) -> anyhow::Error {
+    Error::new(ErrorKind::InvalidRequest, message).into()
+}
+
+// Phase of a timeout error chain: ours carry it, wreq only tells connect timeouts apart
+fn timeout_phase(err: &anyhow::Error) -> Option<TimeoutPhase> {
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<Error>() {
+            return e.phase;
+        }
+        if let Some(e) = cause.downcast_ref::<wreq::Error>() {
+            if e.is_connect() {
+                return Some(TimeoutPhase::Connect);
+            }
+        }
+    }
+    None
+}
+
+/// Work out the category of an error chain
+pub fn classify(err: &anyhow::Error) -> ErrorKind {
+    // Errors raised as `Error` already know their kind
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<Error>() {
+            return e.kind;
+        }
+    }
+
+    for cause in err.chain() {
+        if let Some(e) = cause.downcast_ref::<wreq::Error>() {
+            if e.is_timeout() {
+                return ErrorKind::Timeout;
+            }
+            if e.is_redirect() {
+                return ErrorKind::Redirect;
+            }
+            if e.is_body() || e.is_decode() {
+                return ErrorKind::Body;
+            }
+        }
+
+        if let Some(e) = cause.downcast_ref::<io::Error>() {
+            match e.kind() {
+                io::ErrorKind::TimedOut => return ErrorKind::Timeout,
+                io::ErrorKind::ConnectionRefused => return ErrorKind::ConnectionRefused,
+                io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::UnexpectedEof => return ErrorKind::ConnectionReset,
+                _ => {}
+            }
+        }
+
+        if cause.downcast_ref::<url::ParseError>().is_some() {
+            return ErrorKind::InvalidUrl;
+        }
+    }
+
+    // Resolver, proxy and BoringSSL failures only show up in the messages; only
+    // the innermost one is read, outer ones carry the method and URL as context
+    let message = err.root_cause().to_string().to_lowercase();
+
+    if message.contains("dns error")
+        || message.contains("failed to lookup address")
+        || message.contains("name or service not known")
+        || message.contains("no such host")
+    {
+        ErrorKind::Dns
+    } else if message.contains("proxy") || message.contains("tunnel") {
+        ErrorKind::Proxy
+    } else if message.contains("certificate")
+        || message.contains("tls")
+        || message.contains("ssl")
+        || message.contains("handshake")
+    {
+        ErrorKind::Tls
+    } else if message.contains("relative url")
+        || message.contains("invalid url")
+        || message.contains("invalid uri")
+        || message.contains("empty host")
+    {
+        ErrorKind::InvalidUrl
+    } else if message.contains("connection refused") {
+        ErrorKind::ConnectionRefused
+    } else if message.contains("connection reset") || message.contains("connection closed") {
+        ErrorKind::ConnectionReset
+    } else if message.contains("timed out") {
+        ErrorKind::Timeout
+    } else {
+        ErrorKind::Other
+    }
+}
@@ -0,0 +1,537 @@
+use anyhow::{bail, Context, Result};
+use boring2::hash::{hash, MessageDigest};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpListener;
+
+use crate::profile::Profile;
+
+// Name the captured connections go to (resolved to the loopback listener), so SNI is sent
+const CAPTURE_HOST: &str = "fingerprint.test";
+
+// The client only has to write its first flight to a local socket
+const CAPTURE_TIMEOUT: Duration = Duration::from_secs(5);
+
+const H2_PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+/// TLS and HTTP/2 fingerprints of a profile, in the formats fingerprinting services report
+#[derive(Debug, Clone)]
+pub struct Fingerprint {
+    /// JA3 string: version, ciphers, extensions, groups and point formats, without GREASE
+    pub ja3: String,
+    /// MD5 of `ja3`
+    pub ja3_hash: String,
+    pub ja4: String,
+    /// Akamai HTTP/2 fingerprint: SETTINGS, WINDOW_UPDATE, PRIORITY frames and pseudo-header
+    /// order (none for profiles that don't offer HTTP/2)
+    pub akamai: Option<String>,
+}
+
+/// Fingerprint a profile from what its client sends to a loopback listener: a ClientHello
+/// (never answered) and an HTTP/2 prior-knowledge preface. Nothing leaves the machine
+pub fn fingerprint(profile: &Profile, http2: bool) -> Result<Fingerprint> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .context("Failed to create Tokio runtime")?;
+
+    runtime.block_on(async {
+        let client_hello = capture(profile, false, ClientHello::parse).await?;
+        let akamai = if http2 {
+            Some(capture(profile, true, akamai_fingerprint).await?)
+        } else {
+            None
+        };
+        let ja3 = client_hello.ja3();
+
+        Ok(Fingerprint {
+            ja3_hash: hex_digest(MessageDigest::md5(), &ja3)?,
+            ja3,
+            ja4: client_hello.ja4()?,
+            akamai,
+        })
+    })
+}
+
+// Send a request with the profile to a local listener and parse the first bytes it writes;
+// the request is dropped unanswered once `parse` succeeds
+async fn capture<T>(
+    profile: &Profile,
+    http2_prior_knowledge: bool,
+    parse: impl Fn(&[u8]) -> Option<T>,
+) -> Result<T> {
+    let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+        .await
+        .context("Failed to listen on the loopback interface")?;
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, listener.local_addr()?.port()));
+
+    let mut builder = wreq::Client::builder()
+        .emulation(profile.emulation())
+        .no_proxy()
+        .resolve(CAPTURE_HOST, address);
+    let scheme = if http2_prior_knowledge {
+        builder = builder.http2_only();
+        "http"
+    } else {
+        "https"
+    };
+    let client = builder.build().context("Failed to build HTTP client")?;
+    let request = client
+        .get(format!("{}://{}:{}/", scheme, CAPTURE_HOST, address.port()))
+        .send();
+
+    let captured = async {
+        tokio::select! {
+            captured = read_until(&listener, parse) => captured,
+            sent = request => match sent {
+                Ok(_) => bail!("The fingerprint listener answered a request"),
+                Err(e) => Err(e).context("Failed to send the fingerprint request"),
+            },
+        }
+    };
+
+    tokio::time::timeout(CAPTURE_TIMEOUT, captured)
+        .await
+        .context("Timed out capturing the fingerprint")?
+}
+
+async fn read_until<T>(listener: &TcpListener, parse: impl Fn(&[u8]) -> Option<T>) -> Result<T> {
+    let (mut stream, _) = listener.accept().await?;
+    let mut buffer = Vec::new();
+    let mut chunk = [0; 4096];
+
+    loop {
+        if let Some(parsed) = parse(&buffer) {
+            return Ok(parsed);
+        }
+
+        let read = stream.read(&mut chunk).await?;
+        if read == 0 {
+            bail!("Connection closed before the fingerprint could be read");
+        }
+        buffer.extend_from_slice(&chunk[..read]);
+    }
+}
+
+// Big-endian reader over captured bytes, `None` once they run out
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        let (head, rest) = self.0.split_at_checked(len)?;
+        self.0 = rest;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3)
+            .map(|bytes| usize::from_be_bytes([0, 0, 0, 0, 0, bytes[0], bytes[1], bytes[2]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take(4)
+            .map(|bytes| u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    // Vector with a one or two byte length prefix
+    fn block8(&mut self) -> Option<Reader<'a>> {
+        let len = self.u8()? as usize;
+        self.take(len).map(Reader)
+    }
+
+    fn block16(&mut self) -> Option<Reader<'a>> {
+        let len = self.u16()? as usize;
+        self.take(len).map(Reader)
+    }
+
+    fn u16s(mut self) -> Vec<u16> {
+        std::iter::from_fn(|| self.u16()).collect()
+    }
+}
+
+const HANDSHAKE_RECORD: u8 = 22;
+const CLIENT_HELLO: u8 = 1;
+
+const SERVER_NAME: u16 = 0;
+const SUPPORTED_GROUPS: u16 = 10;
+const EC_POINT_FORMATS: u16 = 11;
+const SIGNATURE_ALGORITHMS: u16 = 13;
+const ALPN: u16 = 16;
+const SUPPORTED_VERSIONS: u16 = 43;
+
+// What JA3 and JA4 read from a ClientHello, in the order it was sent
+#[derive(Debug, Default)]
+struct ClientHello {
+    version: u16,
+    ciphers: Vec<u16>,
+    extensions: Vec<u16>,
+    groups: Vec<u16>,
+    point_formats: Vec<u8>,
+    signature_algorithms: Vec<u16>,
+    supported_versions: Vec<u16>,
+    /// First ALPN protocol
+    alpn: Option<Vec<u8>>,
+}
+
+impl ClientHello {
+    // A handshake record holding a ClientHello, `None` until all of it is there
+    fn parse(record: &[u8]) -> Option<Self> {
+        let mut record = Reader(record);
+        if record.u8()? != HANDSHAKE_RECORD {
+            return None;
+        }
+        record.take(2)?;
+
+        let mut handshake = record.block16()?;
+        if handshake.u8()? != CLIENT_HELLO {
+            return None;
+        }
+        let len = handshake.u24()?;
+        let mut body = Reader(handshake.take(len)?);
+
+        let mut hello = ClientHello {
+            version: body.u16()?,
+            ..Default::default()
+        };
+        body.take(32)?; // random
+        body.block8()?; // session ID
+        hello.ciphers = body.block16()?.u16s();
+        body.block8()?; // compression methods
+
+        let mut extensions = body.block16()?;
+        while !extensions.0.is_empty() {
+            let id = extensions.u16()?;
+            let mut data = extensions.block16()?;
+            hello.extensions.push(id);
+
+            match id {
+                SUPPORTED_GROUPS => hello.groups = data.block16()?.u16s(),
+                EC_POINT_FORMATS => hello.point_formats = data.block8()?.0.to_vec(),
+                SIGNATURE_ALGORITHMS => hello.signature_algorithms = data.block16()?.u16s(),
+                SUPPORTED_VERSIONS => hello.supported_versions = data.block8()?.u16s(),
+                ALPN => hello.alpn = data.block16()?.block8().map(|protocol| protocol.0.to_vec()),
+                _ => {}
+            }
+        }
+
+        Some(hello)
+    }
+
+    // Profiles that permute their extensions (like Chrome) give another one on every connection
+    fn ja3(&self) -> String {
+        let point_formats = self.point_formats.iter().map(u8::to_string);
+
+        format!(
+            "{},{},{},{},{}",
+            self.version,
+            dashed(&self.ciphers),
+            dashed(&self.extensions),
+            dashed(&self.groups),
+            point_formats.collect::<Vec<_>>().join("-")
+        )
+    }
+
+    // https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4.md
+    fn ja4(&self) -> Result<String> {
+        let ciphers = without_grease(&self.ciphers);
+        let extensions = without_grease(&self.extensions);
+
+        let version = without_grease(&self.supported_versions)
+            .into_iter()
+            .max()
+            .unwrap_or(self.version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            _ => "00",
+        };
+        let sni = if extensions.contains(&SERVER_NAME) {
+            'd'
+        } else {
+            'i'
+        };
+        let alpn = match self.alpn.as_deref() {
+            Some(alpn) if !alpn.is_empty() => {
+                let (first, last) = (alpn[0], alpn[alpn.len() - 1]);
+                if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                    format!("{}{}", first as char, last as char)
+                } else {
+                    let (first, last) = (format!("{:02x}", first), format!("{:02x}", last));
+                    format!("{}{}", &first[..1], &last[1..])
+                }
+            }
+            _ => "00".to_string(),
+        };
+
+        let mut sorted_ciphers = ciphers.clone();
+        sorted_ciphers.sort_unstable();
+
+        // SNI and ALPN are left out of the hash, they're in the first part already
+        let mut sorted_extensions: Vec<u16> = extensions
+            .iter()
+            .copied()
+            .filter(|id| *id != SERVER_NAME && *id != ALPN)
+            .collect();
+        sorted_extensions.sort_unstable();
+        let mut extensions_part = hex_list(&sorted_extensions);
+        let signature_algorithms = without_grease(&self.signature_algorithms);
+        if !signature_algorithms.is_empty() {
+            extensions_part = format!("{}_{}", extensions_part, hex_list(&signature_algorithms));
+        }
+
+        Ok(format!(
+            "t{}{}{:02}{:02}{}_{}_{}",
+            version,
+            sni,
+            ciphers.len().min(99),
+            extensions.len().min(99),
+            alpn,
+            truncated_sha256(&sorted_ciphers, &hex_list(&sorted_ciphers))?,
+            truncated_sha256(&sorted_extensions, &extensions_part)?
+        ))
+    }
+}
+
+// RFC 8701 GREASE values: 0x0a0a, 0x1a1a, ... 0xfafa
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn without_grease(values: &[u16]) -> Vec<u16> {
+    values
+        .iter()
+        .copied()
+        .filter(|value| !is_grease(*value))
+        .collect()
+}
+
+fn dashed(values: &[u16]) -> String {
+    let values = without_grease(values);
+    values
+        .iter()
+        .map(u16::to_string)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn hex_list(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|value| format!("{:04x}", value))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// First 12 hex digits of the SHA-256 of `text`, zeros when there is nothing to hash
+fn truncated_sha256(values: &[u16], text: &str) -> Result<String> {
+    if values.is_empty() {
+        return Ok("000000000000".to_string());
+    }
+    let mut digest = hex_digest(MessageDigest::sha256(), text)?;
+    digest.truncate(12);
+    Ok(digest)
+}
+
+fn hex_digest(algorithm: MessageDigest, text: &str) -> Result<String> {
+    let digest = hash(algorithm, text.as_bytes())?;
+    Ok(digest.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+const HEADERS_FRAME: u8 = 1;
+const PRIORITY_FRAME: u8 = 2;
+const SETTINGS_FRAME: u8 = 4;
+const WINDOW_UPDATE_FRAME: u8 = 8;
+
+const FLAG_ACK: u8 = 0x1;
+const FLAG_PADDED: u8 = 0x8;
+const FLAG_PRIORITY: u8 = 0x20;
+
+// `SETTINGS|WINDOW_UPDATE|PRIORITY|pseudo-headers` from the frames ahead of the first
+// request's HEADERS, `None` until that frame is there
+fn akamai_fingerprint(preface: &[u8]) -> Option<String> {
+    let mut frames = Reader(preface.strip_prefix(H2_PREFACE)?);
+    let mut settings = Vec::new();
+    let mut window_update = None;
+    let mut priorities = Vec::new();
+
+    loop {
+        let len = frames.u24()?;
+        let kind = frames.u8()?;
+        let flags = frames.u8()?;
+        let stream = frames.u32()? & 0x7fff_ffff;
+        let mut payload = Reader(frames.take(len)?);
+
+        match kind {
+            SETTINGS_FRAME if flags & FLAG_ACK == 0 => {
+                while let Some(id) = payload.u16() {
+                    settings.push(format!("{}:{}", id, payload.u32()?));
+                }
+            }
+            WINDOW_UPDATE_FRAME if stream == 0 => {
+                window_update = Some(payload.u32()? & 0x7fff_ffff);
+            }
+            PRIORITY_FRAME => {
+                let dependency = payload.u32()?;
+                let weight = payload.u8()? as u16 + 1;
+                priorities.push(format!(
+                    "{}:{}:{}:{}",
+                    stream,
+                    dependency >> 31,
+                    dependency & 0x7fff_ffff,
+                    weight
+                ));
+            }
+            HEADERS_FRAME => {
+                if flags & FLAG_PADDED != 0 {
+                    let padding = payload.u8()? as usize;
+                    let len = payload.0.len().checked_sub(padding)?;
+                    payload.0 = &payload.0[..len];
+                }
+                if flags & FLAG_PRIORITY != 0 {
+                    payload.take(5)?;
+                }
+
+                let window_update =
+                    window_update.map_or_else(|| "00".to_string(), |size| size.to_string());
+                let priorities = if priorities.is_empty() {
+                    "0".to_string()
+                } else {
+                    priorities.join(",")
+                };
+
+                return Some(format!(
+                    "{}|{}|{}|{}",
+                    settings.join(";"),
+                    window_update,
+                    priorities,
+                    pseudo_header_order(payload.0)?
+                ));
+            }
+            _ => {}
+        }
+    }
+}
+
+// Letters of the pseudo-headers leading an HPACK block (`m,a,s,p` for Chrome). A fresh
+// connection's first block can only name them through the static table (indexes 1-7)
+fn pseudo_header_order(block: &[u8]) -> Option<String> {
+    let mut block = Reader(block);
+    let mut order = Vec::new();
+
+    while let Some(&first) = block.0.first() {
+        let name_index = match first {
+            // Indexed field
+            0x80..=0xff => hpack_integer(&mut block, 7)?,
+            // Literal with incremental indexing
+            0x40..=0x7f => hpack_integer(&mut block, 6)?,
+            // Dynamic table size update
+            0x20..=0x3f => {
+                hpack_integer(&mut block, 5)?;
+                continue;
+            }
+            // Literal without indexing or never indexed
+            _ => hpack_integer(&mut block, 4)?,
+        };
+
+        let letter = match name_index {
+            1 => "a",
+            2 | 3 => "m",
+            4 | 5 => "p",
+            6 | 7 => "s",
+            _ => break,
+        };
+        order.push(letter);
+
+        // Literals carry a value after the indexed name
+        if first & 0x80 == 0 {
+            let len = hpack_integer(&mut block, 7)?;
+            block.take(len)?;
+        }
+    }
+
+    Some(order.join(","))
+}
+
+// RFC 7541 5.1: an integer in the low `prefix` bits of the first byte, continued in 7-bit groups
+fn hpack_integer(block: &mut Reader, prefix: u32) -> Option<usize> {
+    let max = u8::MAX >> (8 - prefix);
+    let mut value = (block.u8()? & max) as usize;
+    if value < max as usize {
+        return Some(value);
+    }
+
+    let mut shift = 0;
+    loop {
+        let byte = block.u8()?;
+        value = value.checked_add(((byte & 0x7f) as usize).checked_shl(shift)?)?;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+        shift += 7;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // GREASE in the ciphers, extensions, groups and versions; SNI, ALPN `h2` then `http/1.1`
+    const CLIENT_HELLO_RECORD: &[u8] = &[
+        0x16, 0x03, 0x01, 0x00, 0x76, 0x01, 0x00, 0x00, 0x72, 0x03, 0x03, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x08, 0x0a, 0x0a, 0x13, 0x01, 0x13, 0x02, 0xc0, 0x2b, 0x01, 0x00, 0x00, 0x41, 0x0a, 0x0a,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x08, 0x00, 0x06, 0x1a, 0x1a, 0x00,
+        0x1d, 0x00, 0x17, 0x00, 0x0b, 0x00, 0x02, 0x01, 0x00, 0x00, 0x0d, 0x00, 0x06, 0x00, 0x04,
+        0x04, 0x03, 0x08, 0x04, 0x00, 0x10, 0x00, 0x0e, 0x00, 0x0c, 0x02, 0x68, 0x32, 0x08, 0x68,
+        0x74, 0x74, 0x70, 0x2f, 0x31, 0x2e, 0x31, 0x00, 0x2b, 0x00, 0x07, 0x06, 0x2a, 0x2a, 0x03,
+        0x04, 0x03, 0x03,
+    ];
+
+    #[test]
+    fn client_hello_gives_ja3_and_ja4_without_grease() {
+        assert!(ClientHello::parse(&CLIENT_HELLO_RECORD[..60]).is_none());
+
+        let hello = ClientHello::parse(CLIENT_HELLO_RECORD).unwrap();
+        assert_eq!(hello.ja3(), "771,4865-4866-49195,0-10-11-13-16-43,29-23,0");
+        assert_eq!(
+            hex_digest(MessageDigest::md5(), &hello.ja3()).unwrap(),
+            "11138d9933242c3a03b6aad35a296476"
+        );
+        assert_eq!(hello.ja4().unwrap(), "t13d0306h2_5559582ccdc4_fb71836bce29");
+    }
+
+    #[test]
+    fn akamai_reads_frames_up_to_the_first_headers() {
+        // SETTINGS, WINDOW_UPDATE, then HEADERS with a priority and `:method GET`,
+        // `:authority`, `:scheme https`, `:path /` and a regular header
+        let frames: &[u8] = &[
+            0x00, 0x00, 0x18, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x01, 0x00,
+            0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x04, 0x00, 0x60, 0x00, 0x00, 0x00,
+            0x06, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0xef, 0x00, 0x01, 0x00, 0x00, 0x12, 0x01, 0x24, 0x00, 0x00, 0x00, 0x01, 0x80,
+            0x00, 0x00, 0x00, 0xff, 0x82, 0x41, 0x03, 0x61, 0x2e, 0x62, 0x87, 0x84, 0x40, 0x01,
+            0x78, 0x01, 0x79,
+        ];
+        let preface = [H2_PREFACE, frames].concat();
+
+        assert_eq!(
+            akamai_fingerprint(&preface).as_deref(),
+            Some("1:65536;2:0;4:6291456;6:262144|15663105|0|m,a,s,p")
+        );
+        assert_eq!(akamai_fingerprint(&preface[..preface.len() - 4]), None);
+    }
+}
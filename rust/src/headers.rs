@@ -0,0 +1,270 @@
+use wreq::header::{HeaderMap, HeaderName, HeaderValue, OrigHeaderMap};
+
+/// Request headers in the order they were given, repeated names allowed
+pub type HeaderList = Vec<(String, String)>;
+
+/// How custom headers are ordered against the emulation profile's defaults
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HeaderOrder {
+    /// Keep the profile's order; headers it doesn't know come after its own
+    #[default]
+    Profile,
+    /// Send custom headers first, in the given order and casing, then the
+    /// remaining profile defaults
+    Request,
+}
+
+/// Changes to the default headers the emulation profile sends
+#[derive(Debug, Clone, Default)]
+pub struct ProfileHeaders {
+    /// Send none of the profile's headers (the TLS/HTTP2 fingerprint is kept)
+    pub skip: bool,
+    /// Profile headers to leave out
+    pub remove: Vec<HeaderName>,
+    /// Profile headers to replace at their position (appended when missing)
+    pub replace: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl ProfileHeaders {
+    /// Whether the profile's headers are sent unchanged
+    pub fn is_empty(&self) -> bool {
+        !self.skip && self.remove.is_empty() && self.replace.is_empty()
+    }
+
+    /// Apply the changes to the profile's headers, keeping their order
+    pub fn apply_to(&self, profile: &mut HeaderMap) {
+        if self.skip {
+            profile.clear();
+        }
+
+        for name in &self.remove {
+            profile.remove(name);
+        }
+
+        // `insert` keeps the position of an existing header, repeats are appended
+        let mut replaced: Vec<&HeaderName> = Vec::new();
+        for (name, value) in &self.replace {
+            if replaced.contains(&name) {
+                profile.append(name.clone(), value.clone());
+            } else {
+                profile.insert(name.clone(), value.clone());
+                replaced.push(name);
+            }
+        }
+    }
+
+    /// The profile's headers with the changes applied, as a list
+    pub fn list(&self, profile: &HeaderMap) -> HeaderList {
+        let mut profile = profile.clone();
+        self.apply_to(&mut profile);
+
+        profile
+            .iter()
+            .map(|(name, value)| {
+                (
+                    name.as_str().to_string(),
+                    String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Combine the profile's headers with the custom ones.
+///
+/// A custom header replaces the profile's headers of the same name: at their
+/// position with `HeaderOrder::Profile`, up front with `HeaderOrder::Request`.
+pub fn with_profile(
+    profile: HeaderList,
+    headers: &[(String, String)],
+    order: HeaderOrder,
+) -> HeaderList {
+    let is_custom = |name: &str| {
+        headers
+            .iter()
+            .any(|(custom, _)| custom.eq_ignore_ascii_case(name))
+    };
+
+    if order == HeaderOrder::Request {
+        let mut merged = headers.to_vec();
+        merged.extend(profile.into_iter().filter(|(name, _)| !is_custom(name)));
+        return merged;
+    }
+
+    let mut merged: HeaderList = Vec::with_capacity(profile.len() + headers.len());
+    let mut placed: Vec<String> = Vec::new();
+
+    for (name, value) in profile {
+        if !is_custom(&name) {
+            merged.push((name, value));
+            continue;
+        }
+
+        // All custom values of the name go where the profile had it first
+        if !placed
+            .iter()
+            .any(|placed| placed.eq_ignore_ascii_case(&name))
+        {
+            merged.extend(
+                headers
+                    .iter()
+                    .filter(|(custom, _)| custom.eq_ignore_ascii_case(&name))
+                    .cloned(),
+            );
+            placed.push(name);
+        }
+    }
+
+    merged.extend(
+        headers
+            .iter()
+            .filter(|(name, _)| {
+                !placed
+                    .iter()
+                    .any(|placed| placed.eq_ignore_ascii_case(name))
+            })
+            .cloned(),
+    );
+
+    merged
+}
+
+/// Merge the jar's cookies into the headers.
+///
+/// Custom `Cookie` headers are folded into one (at the position of the first)
+/// followed by the jar's cookies, as browsers send a single `Cookie` header.
+pub fn with_cookies(headers: &[(String, String)], jar_cookies: Option<String>) -> HeaderList {
+    let Some(jar_cookies) = jar_cookies else {
+        return headers.to_vec();
+    };
+
+    let mut merged: HeaderList = Vec::with_capacity(headers.len() + 1);
+    let mut cookie_index = None;
+
+    for (name, value) in headers {
+        if !name.eq_ignore_ascii_case("cookie") {
+            merged.push((name.clone(), value.clone()));
+            continue;
+        }
+
+        match cookie_index {
+            Some(index) => merged[index].1 = format!("{}; {}", merged[index].1, value),
+            None => {
+                cookie_index = Some(merged.len());
+                merged.push((name.clone(), value.clone()));
+            }
+        }
+    }
+
+    match cookie_index {
+        Some(index) => merged[index].1 = format!("{}; {}", merged[index].1, jar_cookies),
+        None => merged.push(("cookie".to_string(), jar_cookies)),
+    }
+
+    merged
+}
+
+/// Header names in order and with their exact casing, for `HeaderOrder::Request`
+pub fn orig_header_map(headers: &[(String, String)]) -> OrigHeaderMap {
+    let mut map = OrigHeaderMap::new();
+    for (name, _) in headers {
+        map.insert(name.clone());
+    }
+    map
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn custom_headers_take_the_place_of_profile_ones() {
+        let profile: HeaderList = [
+            ("user-agent", "Chrome"),
+            ("accept", "*/*"),
+            ("accept-language", "en-US"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .to_vec();
+        let headers = [
+            ("X-Custom", "1"),
+            ("Accept", "text/html"),
+            ("accept", "image/*"),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let lines = |merged: HeaderList| -> Vec<String> {
+            merged
+                .into_iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect()
+        };
+        assert_eq!(
+            lines(with_profile(
+                profile.clone(),
+                &headers,
+                HeaderOrder::Profile
+            )),
+            [
+                "user-agent: Chrome",
+                "Accept: text/html",
+                "accept: image/*",
+                "accept-language: en-US",
+                "X-Custom: 1"
+            ]
+        );
+        assert_eq!(
+            lines(with_profile(profile, &headers, HeaderOrder::Request)),
+            [
+                "X-Custom: 1",
+                "Accept: text/html",
+                "accept: image/*",
+                "user-agent: Chrome",
+                "accept-language: en-US"
+            ]
+        );
+    }
+
+    #[test]
+    fn profile_header_changes_keep_positions() {
+        let mut profile = HeaderMap::new();
+        profile.insert("user-agent", HeaderValue::from_static("Chrome"));
+        profile.insert("accept", HeaderValue::from_static("*/*"));
+        profile.insert("accept-language", HeaderValue::from_static("en-US"));
+
+        let changes = ProfileHeaders {
+            skip: false,
+            remove: vec![HeaderName::from_static("user-agent")],
+            replace: vec![
+                (
+                    HeaderName::from_static("accept"),
+                    HeaderValue::from_static("text/html"),
+                ),
+                (
+                    HeaderName::from_static("x-new"),
+                    HeaderValue::from_static("1"),
+                ),
+                (
+                    HeaderName::from_static("x-new"),
+                    HeaderValue::from_static("2"),
+                ),
+            ],
+        };
+        assert_eq!(
+            changes.list(&profile),
+            [
+                ("accept", "text/html"),
+                ("accept-language", "en-US"),
+                ("x-new", "1"),
+                ("x-new", "2")
+            ]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+        );
+
+        let skip = ProfileHeaders {
+            skip: true,
+            ..ProfileHeaders::default()
+        };
+        assert!(skip.list(&profile).is_empty());
+    }
+}
@@ -1,417 +1,1896 @@
+mod abort;
 mod client;
+mod cookies;
+mod dns;
+mod error;
+mod fingerprint;
+mod headers;
+mod network;
+mod profile;
+mod proxy;
+mod stream;
+mod timeout;
+mod tls;
 mod websocket;
 
-use client::{make_request, RequestOptions, Response};
+use abort::{abortable, get_token, remove_token, store_token, AbortToken};
+use client::{
+    build_client, get_client, http_runtime, init_runtime, make_request, remove_client,
+    store_client, ClientOptions, HttpClient, RedirectMode, RequestBody, RequestOptions, Response,
+    RetryPolicy, DEFAULT_MAX_REDIRECTS, DEFAULT_TIMEOUT_MS, MAX_RETRY_ATTEMPTS,
+};
+use cookies::{get_jar, remove_jar, store_jar, Cookie, CookieJar, StoredCookie};
+use dns::{parse_nameserver, DnsBackend, DnsOptions, DnsOrder, DEFAULT_DOH_URL};
+use error::{Error, ErrorKind};
 use futures_util::StreamExt;
+use headers::{HeaderList, HeaderOrder, ProfileHeaders};
 use neon::prelude::*;
 use neon::types::buffer::TypedArray;
-use std::collections::HashMap;
+use network::NetworkOptions;
+use profile::{
+    builtin_profile_names, custom_profile_names, parse_os, seed_selection, select_profile, Profile,
+    ProfileFilter, ProfileSelector, RANDOM_PROFILE,
+};
+use proxy::{
+    get_pool, remove_pool, store_pool, PoolOptions, ProxyConfig, ProxyPool, ProxySettings,
+    RotationStrategy,
+};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use stream::{get_sink, get_stream, remove_sink, remove_stream, store_sink, BodySink};
+use tls::{ClientIdentity, TlsOptions};
+use url::Url;
 use websocket::{
     connect_websocket, get_connection, remove_connection, store_connection, WebSocketOptions,
     WS_RUNTIME,
 };
+use wreq::header::{HeaderName, HeaderValue};
 use wreq::ws::message::Message;
-use wreq_util::Emulation;
-
-// Parse browser string to Emulation enum
-fn parse_emulation(browser: &str) -> Emulation {
-    match browser {
-        // Chrome
-        "chrome_100" => Emulation::Chrome100,
-        "chrome_101" => Emulation::Chrome101,
-        "chrome_104" => Emulation::Chrome104,
-        "chrome_105" => Emulation::Chrome105,
-        "chrome_106" => Emulation::Chrome106,
-        "chrome_107" => Emulation::Chrome107,
-        "chrome_108" => Emulation::Chrome108,
-        "chrome_109" => Emulation::Chrome109,
-        "chrome_110" => Emulation::Chrome110,
-        "chrome_114" => Emulation::Chrome114,
-        "chrome_116" => Emulation::Chrome116,
-        "chrome_117" => Emulation::Chrome117,
-        "chrome_118" => Emulation::Chrome118,
-        "chrome_119" => Emulation::Chrome119,
-        "chrome_120" => Emulation::Chrome120,
-        "chrome_123" => Emulation::Chrome123,
-        "chrome_124" => Emulation::Chrome124,
-        "chrome_126" => Emulation::Chrome126,
-        "chrome_127" => Emulation::Chrome127,
-        "chrome_128" => Emulation::Chrome128,
-        "chrome_129" => Emulation::Chrome129,
-        "chrome_130" => Emulation::Chrome130,
-        "chrome_131" => Emulation::Chrome131,
-        "chrome_132" => Emulation::Chrome132,
-        "chrome_133" => Emulation::Chrome133,
-        "chrome_134" => Emulation::Chrome134,
-        "chrome_135" => Emulation::Chrome135,
-        "chrome_136" => Emulation::Chrome136,
-        "chrome_137" => Emulation::Chrome137,
-        "chrome_138" => Emulation::Chrome138,
-        "chrome_139" => Emulation::Chrome139,
-        "chrome_140" => Emulation::Chrome140,
-        "chrome_141" => Emulation::Chrome141,
-        "chrome_142" => Emulation::Chrome142,
-        "chrome_143" => Emulation::Chrome143,
-        // Edge
-        "edge_101" => Emulation::Edge101,
-        "edge_122" => Emulation::Edge122,
-        "edge_127" => Emulation::Edge127,
-        "edge_131" => Emulation::Edge131,
-        "edge_134" => Emulation::Edge134,
-        "edge_135" => Emulation::Edge135,
-        "edge_136" => Emulation::Edge136,
-        "edge_137" => Emulation::Edge137,
-        "edge_138" => Emulation::Edge138,
-        "edge_139" => Emulation::Edge134,
-        "edge_140" => Emulation::Edge140,
-        "edge_141" => Emulation::Edge141,
-        "edge_142" => Emulation::Edge142,
-        // Safari
-        "safari_ios_17_2" => Emulation::SafariIos17_2,
-        "safari_ios_17_4_1" => Emulation::SafariIos17_4_1,
-        "safari_ios_16_5" => Emulation::SafariIos16_5,
-        "safari_15_3" => Emulation::Safari15_3,
-        "safari_15_5" => Emulation::Safari15_5,
-        "safari_15_6_1" => Emulation::Safari15_6_1,
-        "safari_16" => Emulation::Safari16,
-        "safari_16_5" => Emulation::Safari16_5,
-        "safari_17_0" => Emulation::Safari17_0,
-        "safari_17_2_1" => Emulation::Safari17_2_1,
-        "safari_17_4_1" => Emulation::Safari17_4_1,
-        "safari_17_5" => Emulation::Safari17_5,
-        "safari_18" => Emulation::Safari18,
-        "safari_ipad_18" => Emulation::SafariIPad18,
-        "safari_18_2" => Emulation::Safari18_2,
-        "safari_ios_18_1_1" => Emulation::SafariIos18_1_1,
-        "safari_18_3" => Emulation::Safari18_3,
-        "safari_18_3_1" => Emulation::Safari18_3_1,
-        "safari_18_5" => Emulation::Safari18_5,
-        "safari_26" => Emulation::Safari26,
-        "safari_26_1" => Emulation::Safari26_1,
-        "safari_26_2" => Emulation::Safari26_2,
-        "safari_ipad_26" => Emulation::SafariIPad26,
-        "safari_ipad_26_2" => Emulation::SafariIpad26_2,
-        "safari_ios_26" => Emulation::SafariIos26,
-        "safari_ios_26_2" => Emulation::SafariIos26_2,
-
-        // Firefox
-        "firefox_109" => Emulation::Firefox109,
-        "firefox_117" => Emulation::Firefox117,
-        "firefox_128" => Emulation::Firefox128,
-        "firefox_133" => Emulation::Firefox133,
-        "firefox_135" => Emulation::Firefox135,
-        "firefox_private_135" => Emulation::FirefoxPrivate135,
-        "firefox_android_135" => Emulation::FirefoxAndroid135,
-        "firefox_136" => Emulation::Firefox136,
-        "firefox_private_136" => Emulation::FirefoxPrivate136,
-        "firefox_139" => Emulation::Firefox139,
-        "firefox_142" => Emulation::Firefox142,
-        "firefox_143" => Emulation::Firefox143,
-        "firefox_144" => Emulation::Firefox144,
-        "firefox_145" => Emulation::Firefox145,
-        "firefox_146" => Emulation::Firefox146,
-        // Opera
-        "opera_116" => Emulation::Opera116,
-        "opera_117" => Emulation::Opera117,
-        "opera_118" => Emulation::Opera118,
-        "opera_119" => Emulation::Opera119,
-        // OkHttp
-        "okhttp_3_9" => Emulation::OkHttp3_9,
-        "okhttp_3_11" => Emulation::OkHttp3_11,
-        "okhttp_3_13" => Emulation::OkHttp3_13,
-        "okhttp_3_14" => Emulation::OkHttp3_14,
-        "okhttp_4_9" => Emulation::OkHttp4_9,
-        "okhttp_4_10" => Emulation::OkHttp4_10,
-        "okhttp_4_12" => Emulation::OkHttp4_12,
-        "okhttp_5" => Emulation::OkHttp5,
-        // Default to Chrome 137
-        _ => Emulation::Chrome137,
+
+// Convert JS object to RequestOptions
+fn js_object_to_request_options(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<RequestOptions> {
+    // Get URL (required)
+    let url: Handle<JsString> = obj.get(cx, "url")?;
+    let url = url.value(cx);
+
+    // Get browser (optional, defaults to chrome_137)
+    let emulation = js_profile(cx, obj)?;
+
+    // Get method (optional, defaults to GET)
+    let method = obj
+        .get_opt(cx, "method")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or_else(|| "GET".to_string());
+
+    // Get headers (optional), how they are ordered against the profile's
+    // and changes to the profile's own
+    let headers = js_headers(cx, obj, "headers")?;
+    let header_order = js_header_order(cx, obj)?;
+    let profile_headers = js_profile_headers(cx, obj)?;
+
+    // Get body (optional): string, Buffer or Uint8Array
+    let mut body = match obj.get_opt::<JsValue, _, _>(cx, "body")? {
+        Some(value) => js_value_to_body(cx, value)?.map(RequestBody::Bytes),
+        None => None,
+    };
+
+    // Get streamed body (optional): file path read in Rust, or chunks written from JS
+    if let Some(path) = obj
+        .get_opt(cx, "bodyFile")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        body = Some(RequestBody::File(PathBuf::from(path)));
+    } else if let Some(sink_obj) = obj.get_opt::<JsObject, _, _>(cx, "bodySink")? {
+        let id = handle_id(cx, sink_obj)?;
+        let sink = match get_sink(id) {
+            Some(sink) => sink,
+            None => return cx.throw_error("Request body stream not found"),
+        };
+        match sink.take_receiver() {
+            Ok(stream) => body = Some(RequestBody::Stream(Arc::new(stream))),
+            Err(e) => {
+                let error = request_error(cx, Error::from_anyhow(&e))?;
+                return cx.throw(error);
+            }
+        }
+    }
+
+    // Get declared content length (optional, streamed bodies only)
+    let content_length = match obj
+        .get_opt(cx, "contentLength")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        Some(length) => Some(js_integer(
+            cx,
+            "contentLength",
+            length,
+            0,
+            MAX_SAFE_INTEGER,
+        )?),
+        None => None,
+    };
+
+    // Get proxy settings or proxy pool (optional)
+    let (proxy, proxy_pool) = js_proxy(cx, obj)?;
+
+    // Get total timeout (optional, `totalTimeout` or its alias `timeout`,
+    // falls back to the client default)
+    let timeout = match js_timeout(cx, obj, "totalTimeout")? {
+        Some(timeout) => Some(timeout),
+        None => js_timeout(cx, obj, "timeout")?,
+    };
+
+    // Get phase timeouts (optional, fall back to the client defaults)
+    let connect_timeout = js_timeout(cx, obj, "connectTimeout")?;
+    let response_header_timeout = js_timeout(cx, obj, "responseHeaderTimeout")?;
+    let read_timeout = js_timeout(cx, obj, "readTimeout")?;
+
+    // Get local binding and address family preference (optional)
+    let network = js_network(cx, obj)?;
+
+    // Get trusted roots, client certificate and verification settings (optional)
+    let tls = js_tls(cx, obj)?;
+
+    // Get stream flag (optional, defaults to false)
+    let stream = obj
+        .get_opt(cx, "stream")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or(false);
+
+    // Get redirect mode (optional, defaults to follow)
+    let redirect = match obj
+        .get_opt(cx, "redirect")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .as_deref()
+    {
+        None | Some("follow") => RedirectMode::Follow,
+        Some("manual") => RedirectMode::Manual,
+        Some("error") => RedirectMode::Error,
+        Some(other) => {
+            return cx.throw_type_error(format!(
+                "Invalid redirect mode: {} (expected 'follow', 'manual' or 'error')",
+                other
+            ))
+        }
+    };
+
+    // Get max redirects (optional, defaults to 10)
+    let max_redirects = match obj
+        .get_opt(cx, "maxRedirects")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        Some(max) => js_integer(cx, "maxRedirects", max, 0, u32::MAX as u64)? as usize,
+        None => DEFAULT_MAX_REDIRECTS,
+    };
+
+    // Get cookie jar (optional, defaults to the client's jar)
+    let cookie_jar = js_cookie_jar(cx, obj)?;
+
+    // Get retry policy (optional)
+    let retry = js_retry_policy(cx, obj)?;
+
+    // Get abort token (optional, created from an AbortSignal on the JS side)
+    let abort = match obj.get_opt::<JsObject, _, _>(cx, "abortToken")? {
+        Some(token_obj) => {
+            let id = handle_id(cx, token_obj)?;
+            match get_token(id) {
+                Some(token) => Some(token),
+                None => return cx.throw_error("Abort token not found"),
+            }
+        }
+        None => None,
+    };
+
+    Ok(RequestOptions {
+        url,
+        emulation,
+        headers,
+        header_order,
+        profile_headers,
+        method,
+        body,
+        content_length,
+        proxy,
+        proxy_pool,
+        timeout,
+        connect_timeout,
+        network,
+        tls,
+        response_header_timeout,
+        read_timeout,
+        stream,
+        redirect,
+        max_redirects,
+        cookie_jar,
+        abort,
+        retry,
+    })
+}
+
+// Resolve `browser`: a name, 'random' or a selector object (unknown names
+// throw unless `lenientBrowser` is set), paired with `os` when given
+fn js_profile(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<Profile> {
+    let selector = js_profile_selector(cx, obj)?;
+
+    let os = match obj
+        .get_opt(cx, "os")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        Some(name) => match parse_os(&name) {
+            Some(os) => Some(os),
+            None => {
+                return cx.throw_type_error(format!(
+                    "Invalid os: {} (expected 'windows', 'macos', 'linux', 'android' or 'ios')",
+                    name
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let lenient = obj
+        .get_opt(cx, "lenientBrowser")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or(false);
+
+    let profile = select_profile(&selector, lenient).and_then(|profile| match os {
+        Some(os) => profile.with_os(os),
+        None => Ok(profile),
+    });
+
+    match profile {
+        Ok(profile) => Ok(profile),
+        Err(e) => {
+            let error = request_error(cx, Error::from_anyhow(&e))?;
+            cx.throw(error)
+        }
+    }
+}
+
+// Convert `browser` into a profile selector (defaults to chrome_137)
+fn js_profile_selector(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<ProfileSelector> {
+    let value = match obj.get_opt::<JsValue, _, _>(cx, "browser")? {
+        Some(value) if !value.is_a::<JsNull, _>(cx) => value,
+        _ => return Ok(ProfileSelector::Name("chrome_137".to_string())),
+    };
+
+    if let Ok(name) = value.downcast::<JsString, _>(cx) {
+        return Ok(match name.value(cx).as_str() {
+            RANDOM_PROFILE => ProfileSelector::Random {
+                filter: ProfileFilter::default(),
+                weights: Vec::new(),
+            },
+            name => ProfileSelector::Name(name.to_string()),
+        });
+    }
+
+    let selector_obj = value.downcast_or_throw::<JsObject, _>(cx)?;
+
+    let family = selector_obj
+        .get_opt(cx, "family")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx));
+
+    let platform = selector_obj
+        .get_opt(cx, "platform")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx));
+
+    let min_version = match selector_obj
+        .get_opt(cx, "minVersion")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        Some(version) => Some(js_integer(cx, "minVersion", version, 0, u32::MAX as u64)? as u32),
+        None => None,
+    };
+
+    let max_version = match selector_obj
+        .get_opt(cx, "maxVersion")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        Some(version) => Some(js_integer(cx, "maxVersion", version, 0, u32::MAX as u64)? as u32),
+        None => None,
+    };
+
+    let mut weights = Vec::new();
+    if let Some(weights_obj) = selector_obj.get_opt::<JsObject, _, _>(cx, "weights")? {
+        for key_val in weights_obj.get_own_property_names(cx)?.to_vec(cx)? {
+            let Ok(key_str) = key_val.downcast::<JsString, _>(cx) else {
+                continue;
+            };
+            let key = key_str.value(cx);
+            let weight: Handle<JsValue> = weights_obj.get(cx, key.as_str())?;
+            let Ok(weight) = weight.downcast::<JsNumber, _>(cx) else {
+                let error = request_error(
+                    cx,
+                    Error::new(
+                        ErrorKind::InvalidRequest,
+                        format!("Weight of {} must be a number", key),
+                    ),
+                )?;
+                return cx.throw(error);
+            };
+            weights.push((key, weight.value(cx)));
+        }
+    }
+
+    Ok(ProfileSelector::Random {
+        filter: ProfileFilter {
+            family,
+            platform,
+            min_version,
+            max_version,
+        },
+        weights,
+    })
+}
+
+// Convert a header option: an ordered array of [name, value] pairs, or an
+// object whose values are strings or arrays of strings (repeated headers)
+fn js_headers(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+    key: &str,
+) -> NeonResult<HeaderList> {
+    let mut headers = HeaderList::new();
+
+    let value = match obj.get_opt::<JsValue, _, _>(cx, key)? {
+        Some(value) if !value.is_a::<JsNull, _>(cx) => value,
+        _ => return Ok(headers),
+    };
+
+    if let Ok(pairs) = value.downcast::<JsArray, _>(cx) {
+        for pair in pairs.to_vec(cx)? {
+            let pair = pair.downcast_or_throw::<JsArray, _>(cx)?;
+            let name: Handle<JsString> = pair.get(cx, 0)?;
+            let value: Handle<JsString> = pair.get(cx, 1)?;
+            headers.push((name.value(cx), value.value(cx)));
+        }
+        return Ok(headers);
+    }
+
+    let headers_obj = value.downcast_or_throw::<JsObject, _>(cx)?;
+    for key_val in headers_obj.get_own_property_names(cx)?.to_vec(cx)? {
+        let Ok(key_str) = key_val.downcast::<JsString, _>(cx) else {
+            continue;
+        };
+        let key = key_str.value(cx);
+        let value: Handle<JsValue> = headers_obj.get(cx, key.as_str())?;
+
+        if let Ok(values) = value.downcast::<JsArray, _>(cx) {
+            for value in values.to_vec(cx)? {
+                if let Ok(value) = value.downcast::<JsString, _>(cx) {
+                    headers.push((key.clone(), value.value(cx)));
+                }
+            }
+        } else if let Ok(value) = value.downcast::<JsString, _>(cx) {
+            headers.push((key, value.value(cx)));
+        }
+    }
+
+    Ok(headers)
+}
+
+// Convert `defaultHeaders: false`, `removeHeaders` and `replaceHeaders`
+fn js_profile_headers(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<ProfileHeaders> {
+    let skip = obj
+        .get_opt(cx, "defaultHeaders")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| !v.value(cx))
+        .unwrap_or(false);
+
+    let mut remove = Vec::new();
+    if let Some(names) = obj.get_opt::<JsArray, _, _>(cx, "removeHeaders")? {
+        for name in names.to_vec(cx)? {
+            let name = name.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+            match HeaderName::from_bytes(name.as_bytes()) {
+                Ok(name) => remove.push(name),
+                Err(_) => return cx.throw_type_error(format!("Invalid header name: {}", name)),
+            }
+        }
+    }
+
+    let mut replace = Vec::new();
+    for (name, value) in js_headers(cx, obj, "replaceHeaders")? {
+        let Ok(header_name) = HeaderName::from_bytes(name.as_bytes()) else {
+            return cx.throw_type_error(format!("Invalid header name: {}", name));
+        };
+        let Ok(header_value) = HeaderValue::from_str(&value) else {
+            return cx.throw_type_error(format!("Invalid value for header {}", name));
+        };
+        replace.push((header_name, header_value));
+    }
+
+    Ok(ProfileHeaders {
+        skip,
+        remove,
+        replace,
+    })
+}
+
+// Convert `headerOrder` ('profile' or 'request', defaults to profile)
+fn js_header_order(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<HeaderOrder> {
+    match obj
+        .get_opt(cx, "headerOrder")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .as_deref()
+    {
+        None | Some("profile") => Ok(HeaderOrder::Profile),
+        Some("request") => Ok(HeaderOrder::Request),
+        Some(other) => cx.throw_type_error(format!(
+            "Invalid header order: {} (expected 'profile' or 'request')",
+            other
+        )),
+    }
+}
+
+// Convert a JS body value to raw bytes
+fn js_value_to_body(
+    cx: &mut FunctionContext,
+    value: Handle<JsValue>,
+) -> NeonResult<Option<Vec<u8>>> {
+    if value.is_a::<JsUndefined, _>(cx) || value.is_a::<JsNull, _>(cx) {
+        return Ok(None);
+    }
+
+    if let Ok(text) = value.downcast::<JsString, _>(cx) {
+        return Ok(Some(text.value(cx).into_bytes()));
+    }
+
+    // Buffer is a Uint8Array subclass, so this covers both
+    if let Ok(bytes) = value.downcast::<JsTypedArray<u8>, _>(cx) {
+        return Ok(Some(bytes.as_slice(cx).to_vec()));
+    }
+
+    cx.throw_type_error("body must be a string, Buffer or Uint8Array")
+}
+
+// Convert JS object to ClientOptions
+fn js_object_to_client_options(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<ClientOptions> {
+    // Get browser (optional, defaults to chrome_137)
+    let emulation = js_profile(cx, obj)?;
+
+    // Get proxy settings or proxy pool (optional)
+    let (proxy, proxy_pool) = js_proxy(cx, obj)?;
+
+    // Get total timeout (optional, `totalTimeout` or `timeout`, defaults to 30000ms)
+    let timeout = match js_timeout(cx, obj, "totalTimeout")? {
+        Some(timeout) => timeout,
+        None => js_timeout(cx, obj, "timeout")?.unwrap_or(DEFAULT_TIMEOUT_MS),
+    };
+
+    // Get phase timeouts (optional)
+    let connect_timeout = js_timeout(cx, obj, "connectTimeout")?;
+    let response_header_timeout = js_timeout(cx, obj, "responseHeaderTimeout")?;
+    let read_timeout = js_timeout(cx, obj, "readTimeout")?;
+
+    // Get local binding and address family preference (optional)
+    let network = js_network(cx, obj)?;
+
+    // Get trusted roots, client certificate and verification settings (optional)
+    let tls = js_tls(cx, obj)?;
+
+    // Get cookie jar (optional, the client gets its own otherwise)
+    let cookie_jar = js_cookie_jar(cx, obj)?;
+
+    Ok(ClientOptions {
+        emulation,
+        proxy,
+        proxy_pool,
+        timeout: Some(timeout),
+        connect_timeout,
+        network,
+        tls,
+        response_header_timeout,
+        read_timeout,
+        cookie_jar,
+    })
+}
+
+// Convert the `retry` option: true, a number of attempts, or an options object
+fn js_retry_policy(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<Option<RetryPolicy>> {
+    let value = match obj.get_opt::<JsValue, _, _>(cx, "retry")? {
+        Some(value) if !value.is_a::<JsNull, _>(cx) => value,
+        _ => return Ok(None),
+    };
+
+    if let Ok(flag) = value.downcast::<JsBoolean, _>(cx) {
+        return Ok(flag.value(cx).then(RetryPolicy::default));
+    }
+
+    if let Ok(attempts) = value.downcast::<JsNumber, _>(cx) {
+        let attempts = attempts.value(cx);
+        let attempts = js_integer(cx, "retry", attempts, 1, MAX_RETRY_ATTEMPTS.into())?;
+        return Ok(Some(RetryPolicy {
+            max_attempts: attempts as u32,
+            ..RetryPolicy::default()
+        }));
+    }
+
+    let retry_obj = value.downcast_or_throw::<JsObject, _>(cx)?;
+    let mut policy = RetryPolicy::default();
+
+    if let Some(attempts) = retry_obj
+        .get_opt(cx, "maxAttempts")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        let attempts = js_integer(
+            cx,
+            "retry.maxAttempts",
+            attempts,
+            1,
+            MAX_RETRY_ATTEMPTS.into(),
+        )?;
+        policy.max_attempts = attempts as u32;
+    }
+
+    if let Some(base_delay) = js_timeout(cx, retry_obj, "baseDelay")? {
+        policy.base_delay_ms = base_delay;
+    }
+
+    if let Some(max_delay) = js_timeout(cx, retry_obj, "maxDelay")? {
+        policy.max_delay_ms = max_delay;
     }
+
+    if let Some(jitter) = retry_obj
+        .get_opt(cx, "jitter")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        policy.jitter = jitter;
+    }
+
+    if let Some(statuses) = retry_obj.get_opt::<JsArray, _, _>(cx, "statuses")? {
+        policy.statuses.clear();
+        for status in statuses.to_vec(cx)? {
+            if let Ok(status) = status.downcast::<JsNumber, _>(cx) {
+                let status = status.value(cx);
+                policy
+                    .statuses
+                    .push(js_integer(cx, "retry.statuses", status, 100, 999)? as u16);
+            }
+        }
+    }
+
+    if let Some(retry_on_error) = retry_obj
+        .get_opt(cx, "retryOnError")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        policy.retry_on_error = retry_on_error;
+    }
+
+    if let Some(non_idempotent) = retry_obj
+        .get_opt(cx, "retryNonIdempotent")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        policy.non_idempotent = non_idempotent;
+    }
+
+    Ok(Some(policy))
+}
+
+// Read an optional timeout or delay in whole milliseconds
+fn js_timeout(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+    key: &str,
+) -> NeonResult<Option<u64>> {
+    let timeout = obj
+        .get_opt(cx, key)?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
+        .map(|v| v.value(cx));
+
+    match timeout {
+        Some(timeout) => Ok(Some(js_integer(cx, key, timeout, 0, MAX_SAFE_INTEGER)?)),
+        None => Ok(None),
+    }
+}
+
+// Read `proxy` (a URL or a settings object) and `proxyPool` (a pool handle)
+// of an options object
+fn js_proxy(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<(Option<ProxyConfig>, Option<Arc<ProxyPool>>)> {
+    let settings = match obj.get_opt::<JsValue, _, _>(cx, "proxy")? {
+        Some(value) => match value.downcast::<JsString, _>(cx) {
+            Ok(url) => Some(ProxySettings::url(url.value(cx))),
+            Err(_) => match value.downcast::<JsObject, _>(cx) {
+                Ok(settings_obj) => Some(js_proxy_settings(cx, settings_obj)?),
+                Err(_) => None,
+            },
+        },
+        None => None,
+    };
+
+    let config = match settings.map(ProxyConfig::new).transpose() {
+        Ok(config) => config,
+        Err(e) => {
+            let error = request_error(cx, Error::from_anyhow(&e))?;
+            return cx.throw(error);
+        }
+    };
+
+    let pool = match obj.get_opt::<JsObject, _, _>(cx, "proxyPool")? {
+        Some(pool_obj) => {
+            let id = handle_id(cx, pool_obj)?;
+            match get_pool(id) {
+                Some(pool) => Some(pool),
+                None => return cx.throw_error("Proxy pool not found (was it closed?)"),
+            }
+        }
+        None => None,
+    };
+
+    Ok((config, pool))
+}
+
+// Convert a proxy settings object: per-scheme URLs, credentials, bypass list
+fn js_proxy_settings(
+    cx: &mut FunctionContext,
+    settings_obj: Handle<JsObject>,
+) -> NeonResult<ProxySettings> {
+    let mut settings = ProxySettings::default();
+
+    for (key, field) in [
+        ("url", &mut settings.all),
+        ("http", &mut settings.http),
+        ("https", &mut settings.https),
+        ("username", &mut settings.username),
+        ("password", &mut settings.password),
+        ("authorization", &mut settings.authorization),
+    ] {
+        *field = settings_obj
+            .get_opt(cx, key)?
+            .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+            .map(|v| v.value(cx));
+    }
+
+    // Bypass list: an array of entries, or a comma separated string like NO_PROXY
+    if let Some(value) = settings_obj.get_opt::<JsValue, _, _>(cx, "bypass")? {
+        if let Ok(list) = value.downcast::<JsString, _>(cx) {
+            settings.bypass = list
+                .value(cx)
+                .split(',')
+                .map(|entry| entry.trim().to_string())
+                .filter(|entry| !entry.is_empty())
+                .collect();
+        } else if let Ok(list) = value.downcast::<JsArray, _>(cx) {
+            for entry in list.to_vec(cx)? {
+                let entry = entry.downcast_or_throw::<JsString, _>(cx)?;
+                settings.bypass.push(entry.value(cx));
+            }
+        }
+    }
+
+    settings.from_env = settings_obj
+        .get_opt(cx, "fromEnv")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or(false);
+
+    Ok(settings)
+}
+
+// Read `localAddress`, `interface` and the DNS settings (`dnsOrder`, `resolve`,
+// `dns`) of an options object
+fn js_network(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<NetworkOptions> {
+    let local_address = match obj
+        .get_opt(cx, "localAddress")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        Some(address) => match address.parse() {
+            Ok(address) => Some(address),
+            Err(_) => {
+                return cx.throw_type_error(format!(
+                    "Invalid localAddress: {} (expected an IPv4 or IPv6 address)",
+                    address
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let interface = obj
+        .get_opt(cx, "interface")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx));
+
+    let dns_order = match obj
+        .get_opt(cx, "dnsOrder")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+    {
+        Some(name) => match DnsOrder::parse(&name) {
+            Some(order) => order,
+            None => {
+                return cx.throw_type_error(format!(
+                    "Invalid dnsOrder: {} (expected 'verbatim', 'ipv4first' or 'ipv6first')",
+                    name
+                ))
+            }
+        },
+        None => DnsOrder::default(),
+    };
+
+    let mut dns = match obj.get_opt::<JsObject, _, _>(cx, "dns")? {
+        Some(dns_obj) => js_dns(cx, dns_obj)?,
+        None => DnsOptions::default(),
+    };
+    dns.order = dns_order;
+
+    // Fixed addresses: `{ 'host': '1.2.3.4' }` or a list of addresses per host
+    if let Some(resolve_obj) = obj.get_opt::<JsObject, _, _>(cx, "resolve")? {
+        for key_val in resolve_obj.get_own_property_names(cx)?.to_vec(cx)? {
+            let Ok(key_str) = key_val.downcast::<JsString, _>(cx) else {
+                continue;
+            };
+            let key = key_str.value(cx);
+            let value: Handle<JsValue> = resolve_obj.get(cx, key.as_str())?;
+
+            let mut addresses = Vec::new();
+            if let Ok(address) = value.downcast::<JsString, _>(cx) {
+                addresses.push(address.value(cx));
+            } else if let Ok(list) = value.downcast::<JsArray, _>(cx) {
+                for address in list.to_vec(cx)? {
+                    let address = address.downcast_or_throw::<JsString, _>(cx)?;
+                    addresses.push(address.value(cx));
+                }
+            }
+
+            if let Err(e) = dns.add_override(&key, &addresses) {
+                let error = request_error(cx, Error::from_anyhow(&e))?;
+                return cx.throw(error);
+            }
+        }
+    }
+
+    Ok(NetworkOptions {
+        local_address,
+        interface,
+        dns,
+    })
+}
+
+// Convert a `dns` settings object: resolver, nameservers, DoH endpoint, cache TTL
+fn js_dns(cx: &mut FunctionContext, dns_obj: Handle<JsObject>) -> NeonResult<DnsOptions> {
+    let resolver = dns_obj
+        .get_opt(cx, "resolver")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or_else(|| "system".to_string());
+
+    let mut nameservers = Vec::new();
+    if let Some(list) = dns_obj.get_opt::<JsArray, _, _>(cx, "nameservers")? {
+        for nameserver in list.to_vec(cx)? {
+            let nameserver = nameserver.downcast_or_throw::<JsString, _>(cx)?.value(cx);
+            match parse_nameserver(&nameserver) {
+                Some(nameserver) => nameservers.push(nameserver),
+                None => {
+                    return cx.throw_type_error(format!(
+                        "Invalid nameserver: {} (expected an IP address, optionally with a port)",
+                        nameserver
+                    ))
+                }
+            }
+        }
+    }
+
+    let doh_url = dns_obj
+        .get_opt(cx, "dohUrl")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx));
+
+    let backend = match resolver.as_str() {
+        "system" => DnsBackend::System,
+        "hickory" => DnsBackend::Hickory { nameservers },
+        "doh" => {
+            let doh_url = doh_url.as_deref().unwrap_or(DEFAULT_DOH_URL);
+            match Url::parse(doh_url) {
+                Ok(url) if url.scheme() == "https" || url.scheme() == "http" => {
+                    DnsBackend::Doh { url }
+                }
+                _ => return cx.throw_type_error(format!("Invalid dohUrl: {}", doh_url)),
+            }
+        }
+        _ => {
+            return cx.throw_type_error(format!(
+                "Invalid DNS resolver: {} (expected 'system', 'hickory' or 'doh')",
+                resolver
+            ))
+        }
+    };
+
+    let cache_ttl = js_timeout(cx, dns_obj, "cacheTtl")?.map(Duration::from_millis);
+
+    Ok(DnsOptions {
+        backend,
+        cache_ttl,
+        ..DnsOptions::default()
+    })
+}
+
+// Largest integer a JS number holds exactly (`Number.MAX_SAFE_INTEGER`)
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+// Check a number option is a whole number in `min..=max` (an `as` cast would
+// turn negative, fractional or NaN values into 0 or a truncated value)
+fn js_integer(
+    cx: &mut FunctionContext,
+    name: &str,
+    value: f64,
+    min: u64,
+    max: u64,
+) -> NeonResult<u64> {
+    if value.fract() == 0.0 && value >= min as f64 && value <= max as f64 {
+        return Ok(value as u64);
+    }
+
+    let error = request_error(
+        cx,
+        Error::new(
+            ErrorKind::InvalidRequest,
+            format!(
+                "{} must be a whole number from {} to {} (got {})",
+                name, min, max, value
+            ),
+        ),
+    )?;
+    cx.throw(error)
+}
+
+// Read the `tls` settings object of an options object: extra roots, system
+// store, client certificate (PEM or PKCS#12) and verification toggle
+fn js_tls(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<TlsOptions> {
+    let Some(tls_obj) = obj.get_opt::<JsObject, _, _>(cx, "tls")? else {
+        return Ok(TlsOptions::default());
+    };
+
+    // Roots: a PEM bundle, or a list of them
+    let mut ca = Vec::new();
+    if let Some(value) = tls_obj.get_opt::<JsValue, _, _>(cx, "ca")? {
+        if let Ok(list) = value.downcast::<JsArray, _>(cx) {
+            for pem in list.to_vec(cx)? {
+                ca.push(js_pem(cx, pem, "ca")?);
+            }
+        } else {
+            ca.push(js_pem(cx, value, "ca")?);
+        }
+    }
+
+    let system_ca = tls_obj
+        .get_opt(cx, "systemCa")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or(false);
+
+    let cert = match tls_obj.get_opt::<JsValue, _, _>(cx, "cert")? {
+        Some(value) => Some(js_pem(cx, value, "cert")?),
+        None => None,
+    };
+    let key = match tls_obj.get_opt::<JsValue, _, _>(cx, "key")? {
+        Some(value) => Some(js_pem(cx, value, "key")?),
+        None => None,
+    };
+    let pfx = tls_obj
+        .get_opt(cx, "pfx")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsTypedArray<u8>, _>(cx).ok())
+        .map(|v| v.as_slice(cx).to_vec());
+    let passphrase = tls_obj
+        .get_opt(cx, "passphrase")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or_default();
+
+    let identity = match (cert, key, pfx) {
+        (None, None, None) => None,
+        (Some(cert), Some(key), None) => Some(ClientIdentity::Pem { cert, key }),
+        (None, None, Some(der)) => Some(ClientIdentity::Pkcs12 { der, passphrase }),
+        (_, _, Some(_)) => {
+            return cx.throw_type_error("tls.pfx can't be combined with tls.cert or tls.key")
+        }
+        _ => return cx.throw_type_error("tls.cert and tls.key must be given together"),
+    };
+
+    let insecure_skip_verify = tls_obj
+        .get_opt(cx, "insecureSkipVerify")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsBoolean, _>(cx).ok())
+        .map(|v| v.value(cx))
+        .unwrap_or(false);
+
+    Ok(TlsOptions {
+        ca,
+        system_ca,
+        identity,
+        insecure_skip_verify,
+    })
+}
+
+// PEM content of a TLS option, given as a string or a Buffer
+fn js_pem(cx: &mut FunctionContext, value: Handle<JsValue>, key: &str) -> NeonResult<Vec<u8>> {
+    if let Ok(pem) = value.downcast::<JsString, _>(cx) {
+        return Ok(pem.value(cx).into_bytes());
+    }
+    if let Ok(bytes) = value.downcast::<JsTypedArray<u8>, _>(cx) {
+        return Ok(bytes.as_slice(cx).to_vec());
+    }
+
+    cx.throw_type_error(format!("tls.{} must be a string or a Buffer", key))
+}
+
+// Resolve the optional `cookieJar` handle of an options object
+fn js_cookie_jar(
+    cx: &mut FunctionContext,
+    obj: Handle<JsObject>,
+) -> NeonResult<Option<Arc<CookieJar>>> {
+    match obj.get_opt::<JsObject, _, _>(cx, "cookieJar")? {
+        Some(jar_obj) => {
+            let id = handle_id(cx, jar_obj)?;
+            match get_jar(id) {
+                Some(jar) => Ok(Some(jar)),
+                None => cx.throw_error("Cookie jar not found"),
+            }
+        }
+        None => Ok(None),
+    }
+}
+
+// Convert a jar cookie to a JS object
+fn stored_cookie_to_js_object<'a, C: Context<'a>>(
+    cx: &mut C,
+    cookie: &StoredCookie,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    let name = cx.string(&cookie.name);
+    obj.set(cx, "name", name)?;
+
+    let value = cx.string(&cookie.value);
+    obj.set(cx, "value", value)?;
+
+    let domain = cx.string(&cookie.domain);
+    obj.set(cx, "domain", domain)?;
+
+    let host_only = cx.boolean(cookie.host_only);
+    obj.set(cx, "hostOnly", host_only)?;
+
+    let path = cx.string(&cookie.path);
+    obj.set(cx, "path", path)?;
+
+    if let Some(expires) = cookie.expires {
+        let expires = cx.date(expires as f64 * 1000.0).or_throw(cx)?;
+        obj.set(cx, "expires", expires)?;
+    }
+
+    let secure = cx.boolean(cookie.secure);
+    obj.set(cx, "secure", secure)?;
+
+    let http_only = cx.boolean(cookie.http_only);
+    obj.set(cx, "httpOnly", http_only)?;
+
+    if let Some(same_site) = &cookie.same_site {
+        let same_site = cx.string(same_site);
+        obj.set(cx, "sameSite", same_site)?;
+    }
+
+    Ok(obj)
+}
+
+// Convert a parsed cookie to a JS object (optional attributes are left out)
+fn cookie_to_js_object<'a, C: Context<'a>>(cx: &mut C, cookie: &Cookie) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    let name = cx.string(&cookie.name);
+    obj.set(cx, "name", name)?;
+
+    let value = cx.string(&cookie.value);
+    obj.set(cx, "value", value)?;
+
+    if let Some(domain) = &cookie.domain {
+        let domain = cx.string(domain);
+        obj.set(cx, "domain", domain)?;
+    }
+
+    if let Some(path) = &cookie.path {
+        let path = cx.string(path);
+        obj.set(cx, "path", path)?;
+    }
+
+    if let Some(expires) = cookie.expires {
+        let expires = cx.date(expires as f64 * 1000.0).or_throw(cx)?;
+        obj.set(cx, "expires", expires)?;
+    }
+
+    if let Some(max_age) = cookie.max_age {
+        let max_age = cx.number(max_age as f64);
+        obj.set(cx, "maxAge", max_age)?;
+    }
+
+    let secure = cx.boolean(cookie.secure);
+    obj.set(cx, "secure", secure)?;
+
+    let http_only = cx.boolean(cookie.http_only);
+    obj.set(cx, "httpOnly", http_only)?;
+
+    if let Some(same_site) = &cookie.same_site {
+        let same_site = cx.string(same_site);
+        obj.set(cx, "sameSite", same_site)?;
+    }
+
+    let raw = cx.string(&cookie.raw);
+    obj.set(cx, "raw", raw)?;
+
+    Ok(obj)
+}
+
+// Throw a JS Error carrying `code`, `url`, `method` and `cause` properties
+fn throw_request_error<'a, C: Context<'a>, T: Value>(cx: &mut C, error: Error) -> JsResult<'a, T> {
+    let js_error = request_error(cx, error)?;
+    cx.throw(js_error)
+}
+
+// Build the JS Error thrown by `throw_request_error`
+fn request_error<'a, C: Context<'a>>(cx: &mut C, error: Error) -> JsResult<'a, JsError> {
+    let js_error = cx.error(&error.message)?;
+
+    let code = cx.string(error.kind.code());
+    js_error.set(cx, "code", code)?;
+
+    if let Some(url) = &error.url {
+        let url = cx.string(url);
+        js_error.set(cx, "url", url)?;
+    }
+
+    if let Some(method) = &error.method {
+        let method = cx.string(method);
+        js_error.set(cx, "method", method)?;
+    }
+
+    if let Some(cause) = &error.cause {
+        let cause = cx.error(cause)?;
+        js_error.set(cx, "cause", cause)?;
+    }
+
+    if let Some(phase) = error.phase {
+        let phase = cx.string(phase.as_str());
+        js_error.set(cx, "phase", phase)?;
+    }
+
+    Ok(js_error)
+}
+
+// Read the `_id` of a native handle object (client, WebSocket, ...)
+fn handle_id(cx: &mut FunctionContext, obj: Handle<JsObject>) -> NeonResult<u64> {
+    let id_val: Handle<JsNumber> = obj.get(cx, "_id")?;
+    Ok(id_val.value(cx) as u64)
+}
+
+// Convert Response to JS object
+fn response_to_js_object<'a, C: Context<'a>>(
+    cx: &mut C,
+    response: Response,
+) -> JsResult<'a, JsObject> {
+    let obj = cx.empty_object();
+
+    // Status
+    let status = cx.number(response.status as f64);
+    obj.set(cx, "status", status)?;
+
+    // URL
+    let url = cx.string(&response.url);
+    obj.set(cx, "url", url)?;
+
+    // Headers
+    let headers_obj = cx.empty_object();
+    // for (key, value) in response.headers {
+    //     let value_str = cx.string(&value);
+    //     headers_obj.set(cx, key.as_str(), value_str)?;
+    // }
+    for (key, values) in &response.headers {
+        // 借用 HashMap，避免所有权转移
+        let js_value: Handle<JsValue> = if values.len() == 1 {
+            // 单值：直接转为字符串
+            cx.string(&values[0]).upcast()
+        } else {
+            // 多值：转为 JS 数组
+            let js_array = cx.empty_array();
+            for (i, value) in values.iter().enumerate() {
+                let js_str = cx.string(value);
+                js_array.set(cx, i as u32, js_str)?;
+            }
+            js_array.upcast()
+        };
+        headers_obj.set(cx, key.as_str(), js_value)?;
+    }
+    obj.set(cx, "headers", headers_obj)?;
+
+    // Cookies, one object per Set-Cookie header
+    let cookies_arr = cx.empty_array();
+    for (i, cookie) in response.cookies.iter().enumerate() {
+        let cookie_obj = cookie_to_js_object(cx, cookie)?;
+        cookies_arr.set(cx, i as u32, cookie_obj)?;
+    }
+    obj.set(cx, "cookies", cookies_arr)?;
+
+    // Redirect chain
+    let redirects_arr = cx.empty_array();
+    for (i, hop) in response.redirects.iter().enumerate() {
+        let hop_obj = cx.empty_object();
+
+        let url = cx.string(&hop.url);
+        hop_obj.set(cx, "url", url)?;
+
+        let status = cx.number(hop.status as f64);
+        hop_obj.set(cx, "status", status)?;
+
+        let location = cx.string(&hop.location);
+        hop_obj.set(cx, "location", location)?;
+
+        let set_cookies = cx.empty_array();
+        for (j, value) in hop.set_cookies.iter().enumerate() {
+            let js_str = cx.string(value);
+            set_cookies.set(cx, j as u32, js_str)?;
+        }
+        hop_obj.set(cx, "setCookies", set_cookies)?;
+
+        redirects_arr.set(cx, i as u32, hop_obj)?;
+    }
+    obj.set(cx, "redirects", redirects_arr)?;
+
+    // Attempts made (more than one when retried)
+    let attempts = cx.number(response.attempts as f64);
+    obj.set(cx, "attempts", attempts)?;
+
+    let profile = cx.string(&response.profile);
+    obj.set(cx, "profile", profile)?;
+
+    if let Some(proxy) = &response.proxy {
+        let proxy = cx.string(proxy);
+        obj.set(cx, "proxy", proxy)?;
+    }
+
+    // Body (raw bytes as Buffer, or a stream handle to read it from)
+    match response.stream_id {
+        Some(stream_id) => {
+            let stream_obj = cx.empty_object();
+            let id_num = cx.number(stream_id as f64);
+            stream_obj.set(cx, "_id", id_num)?;
+            obj.set(cx, "_stream", stream_obj)?;
+        }
+        None => {
+            let mut body = cx.buffer(response.body.len())?;
+            body.as_mut_slice(cx).copy_from_slice(&response.body);
+            obj.set(cx, "body", body)?;
+        }
+    }
+
+    Ok(obj)
+}
+
+// Main request function exported to Node.js
+fn request(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    // Get the options object
+    let options_obj = cx.argument::<JsObject>(0)?;
+
+    // Convert JS object to Rust struct
+    let options = js_object_to_request_options(&mut cx, options_obj)?;
+
+    // One-off request: the client is built from the request options
+    execute_request(&mut cx, None, options)
+}
+
+// Options fixed when a client is built, rejected on its requests rather than ignored
+const CLIENT_FIXED_OPTIONS: &[&str] = &[
+    "browser",
+    "lenientBrowser",
+    "os",
+    "proxy",
+    "proxyPool",
+    "connectTimeout",
+    "localAddress",
+    "interface",
+    "dnsOrder",
+    "resolve",
+    "dns",
+    "tls",
+];
+
+// Request through a client created with `createClient`
+fn client_request(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let client_obj = cx.argument::<JsObject>(0)?;
+    let options_obj = cx.argument::<JsObject>(1)?;
+
+    for key in CLIENT_FIXED_OPTIONS {
+        if options_obj
+            .get_opt::<JsValue, _, _>(&mut cx, *key)?
+            .is_some()
+        {
+            let error = Error::new(
+                ErrorKind::InvalidRequest,
+                format!(
+                    "{} is fixed when the client is created and can't be set per request",
+                    key
+                ),
+            );
+            return throw_request_error(&mut cx, error);
+        }
+    }
+
+    let id = handle_id(&mut cx, client_obj)?;
+
+    // Get client from global storage
+    let client = match get_client(id) {
+        Some(client) => client,
+        None => return cx.throw_error("Client not found (was it closed?)"),
+    };
+
+    let options = js_object_to_request_options(&mut cx, options_obj)?;
+
+    execute_request(&mut cx, Some(client), options)
+}
+
+// Run a request as a task on the shared runtime and settle the returned promise
+fn execute_request(
+    cx: &mut FunctionContext,
+    client: Option<HttpClient>,
+    options: RequestOptions,
+) -> JsResult<JsPromise> {
+    // Kept for error reporting, options are moved into the request
+    let method = if options.method.is_empty() {
+        "GET".to_string()
+    } else {
+        options.method.to_uppercase()
+    };
+    let url = options.url.clone();
+    let abort = options.abort.clone();
+
+    // Create a promise
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    http_runtime().spawn(async move {
+        // Make the request (dropped mid-flight if aborted)
+        let result = abortable(abort.as_deref(), async move {
+            let client = match client {
+                Some(client) => client,
+                None => build_client(&options.client_options())?,
+            };
+            make_request(&client, options).await
+        })
+        .await;
+
+        // Send result back to JS
+        deferred.settle_with(&channel, move |mut cx| {
+            match result {
+                Ok(response) => response_to_js_object(&mut cx, response),
+                Err(e) => {
+                    // Structured error with the full chain as message
+                    let error = Error::from_anyhow(&e).with_request(method, url);
+                    throw_request_error(&mut cx, error)
+                }
+            }
+        });
+    });
+
+    Ok(promise)
+}
+
+// Read the next chunk of a streamed response body (resolves null at the end)
+fn stream_read(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let stream_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(&mut cx, stream_obj)?;
+
+    let stream = match get_stream(id) {
+        Some(stream) => stream,
+        None => return cx.throw_error("Response body stream not found (already consumed?)"),
+    };
+
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
+
+    http_runtime().spawn(async move {
+        let result = stream.next_chunk().await;
+
+        // Release the stream once it is finished or broken
+        if !matches!(result, Ok(Some(_))) {
+            remove_stream(id);
+        }
+
+        deferred.settle_with(&channel, move |mut cx| match result {
+            Ok(Some(chunk)) => {
+                let mut buffer = cx.buffer(chunk.len())?;
+                buffer.as_mut_slice(&mut cx).copy_from_slice(&chunk);
+                Ok(buffer.upcast::<JsValue>())
+            }
+            Ok(None) => Ok(cx.null().upcast::<JsValue>()),
+            Err(e) => {
+                let error = Error::from_anyhow(&e).or_kind(ErrorKind::Body);
+                throw_request_error(&mut cx, error)
+            }
+        });
+    });
+
+    Ok(promise)
+}
+
+// Stop reading a streamed response body and close its connection
+fn stream_close(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let stream_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(&mut cx, stream_obj)?;
+
+    remove_stream(id);
+
+    Ok(cx.undefined())
+}
+
+// Create a sink that a streamed request body is written into from JS
+fn body_sink_create(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let id = store_sink(BodySink::new());
+
+    let obj = cx.empty_object();
+    let id_num = cx.number(id as f64);
+    obj.set(&mut cx, "_id", id_num)?;
+    Ok(obj)
 }
 
-// Convert JS object to RequestOptions
-fn js_object_to_request_options(
-    cx: &mut FunctionContext,
-    obj: Handle<JsObject>,
-) -> NeonResult<RequestOptions> {
-    // Get URL (required)
-    let url: Handle<JsString> = obj.get(cx, "url")?;
-    let url = url.value(cx);
+// Write a chunk to a request body sink (resolves once it has been queued)
+fn body_sink_write(mut cx: FunctionContext) -> JsResult<JsPromise> {
+    let sink_obj = cx.argument::<JsObject>(0)?;
+    let chunk = cx.argument::<JsTypedArray<u8>>(1)?;
 
-    // Get browser (optional, defaults to chrome_137)
-    let browser_str = obj
-        .get_opt(cx, "browser")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx))
-        .unwrap_or_else(|| "chrome_137".to_string());
+    let id = handle_id(&mut cx, sink_obj)?;
+    let chunk = chunk.as_slice(&cx).to_vec();
 
-    let emulation = parse_emulation(&browser_str);
+    let sink = match get_sink(id) {
+        Some(sink) => sink,
+        None => return cx.throw_error("Request body stream not found"),
+    };
 
-    // Get method (optional, defaults to GET)
-    let method = obj
-        .get_opt(cx, "method")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx))
-        .unwrap_or_else(|| "GET".to_string());
+    let channel = cx.channel();
+    let (deferred, promise) = cx.promise();
 
-    // Get headers (optional)
-    let mut headers = HashMap::new();
-    if let Ok(Some(headers_obj)) = obj.get_opt::<JsObject, _, _>(cx, "headers") {
-        let keys = headers_obj.get_own_property_names(cx)?;
-        let keys_vec = keys.to_vec(cx)?;
-
-        for key_val in keys_vec {
-            if let Ok(key_str) = key_val.downcast::<JsString, _>(cx) {
-                let key = key_str.value(cx);
-                if let Ok(value) = headers_obj.get::<JsString, _, _>(cx, key.as_str()) {
-                    headers.insert(key, value.value(cx));
-                }
+    http_runtime().spawn(async move {
+        let result = sink.write(chunk).await;
+
+        deferred.settle_with(&channel, move |mut cx| match result {
+            Ok(()) => Ok(cx.undefined()),
+            Err(e) => {
+                let error = Error::from_anyhow(&e).or_kind(ErrorKind::Body);
+                throw_request_error(&mut cx, error)
             }
+        });
+    });
+
+    Ok(promise)
+}
+
+// Mark the end of a streamed request body
+fn body_sink_finish(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let sink_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(&mut cx, sink_obj)?;
+
+    if let Some(sink) = get_sink(id) {
+        sink.finish();
+    }
+    remove_sink(id);
+
+    Ok(cx.undefined())
+}
+
+// Fail the request a streamed body belongs to (e.g. the JS source threw)
+fn body_sink_abort(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let sink_obj = cx.argument::<JsObject>(0)?;
+    let message = cx.argument::<JsString>(1)?.value(&mut cx);
+    let id = handle_id(&mut cx, sink_obj)?;
+
+    if let Some(sink) = get_sink(id) {
+        http_runtime().spawn(async move { sink.abort(message).await });
+    }
+    remove_sink(id);
+
+    Ok(cx.undefined())
+}
+
+// Upper bound for `workerThreads`, well past any core count
+const MAX_WORKER_THREADS: u64 = 1024;
+
+// Configure the shared HTTP runtime (optional, before the first request)
+fn init(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let mut worker_threads = None;
+
+    if let Some(value) = cx.argument_opt(0) {
+        let options_obj = value.downcast_or_throw::<JsObject, _>(&mut cx)?;
+        let threads = options_obj
+            .get_opt(&mut cx, "workerThreads")?
+            .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(&mut cx).ok())
+            .map(|v| v.value(&mut cx));
+        if let Some(threads) = threads {
+            let threads = js_integer(&mut cx, "workerThreads", threads, 1, MAX_WORKER_THREADS)?;
+            worker_threads = Some(threads as usize);
         }
     }
 
-    // Get body (optional)
-    let body = obj
-        .get_opt(cx, "body")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx));
+    if let Err(e) = init_runtime(worker_threads) {
+        return throw_request_error(&mut cx, Error::from_anyhow(&e));
+    }
 
-    // Get proxy (optional)
-    let proxy = obj
-        .get_opt(cx, "proxy")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(cx).ok())
-        .map(|v| v.value(cx));
+    Ok(cx.undefined())
+}
 
-    // Get timeout (optional, defaults to 30000ms)
-    let timeout = obj
-        .get_opt(cx, "timeout")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(cx).ok())
-        .map(|v| v.value(cx) as u64)
-        .unwrap_or(30000);
+// Create a reusable client (connection pool, TLS sessions, cookies)
+fn create_client(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let options = match cx.argument_opt(0) {
+        Some(value) => {
+            let options_obj = value.downcast_or_throw::<JsObject, _>(&mut cx)?;
+            js_object_to_client_options(&mut cx, options_obj)?
+        }
+        None => ClientOptions {
+            emulation: Profile::default(),
+            proxy: None,
+            proxy_pool: None,
+            timeout: Some(DEFAULT_TIMEOUT_MS),
+            connect_timeout: None,
+            network: NetworkOptions::default(),
+            tls: TlsOptions::default(),
+            response_header_timeout: None,
+            read_timeout: None,
+            cookie_jar: None,
+        },
+    };
 
-    Ok(RequestOptions {
-        url,
-        emulation,
-        headers,
-        method,
-        body,
-        proxy,
-        timeout,
-    })
+    // Connection pool is bound to the runtime requests will run on
+    let _guard = http_runtime().enter();
+
+    let client = match build_client(&options) {
+        Ok(client) => client,
+        Err(e) => return throw_request_error(&mut cx, Error::from_anyhow(&e)),
+    };
+
+    let id = store_client(client);
+
+    let obj = cx.empty_object();
+    let id_num = cx.number(id as f64);
+    obj.set(&mut cx, "_id", id_num)?;
+    Ok(obj)
 }
 
-// Convert Response to JS object
-fn response_to_js_object<'a, C: Context<'a>>(
-    cx: &mut C,
-    response: Response,
-) -> JsResult<'a, JsObject> {
+// Drop a client and its connection pool
+fn client_close(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let client_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(&mut cx, client_obj)?;
+
+    remove_client(id);
+
+    Ok(cx.undefined())
+}
+
+// Create an abort token for a request
+fn abort_token_create(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let id = store_token(Arc::new(AbortToken::new()));
+
     let obj = cx.empty_object();
+    let id_num = cx.number(id as f64);
+    obj.set(&mut cx, "_id", id_num)?;
+    Ok(obj)
+}
 
-    // Status
-    let status = cx.number(response.status as f64);
-    obj.set(cx, "status", status)?;
+// Abort every request using the token
+fn abort_token_abort(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let token_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(&mut cx, token_obj)?;
 
-    // URL
-    let url = cx.string(&response.url);
-    obj.set(cx, "url", url)?;
+    if let Some(token) = get_token(id) {
+        token.abort();
+    }
 
-    // Headers
-    let headers_obj = cx.empty_object();
-    // for (key, value) in response.headers {
-    //     let value_str = cx.string(&value);
-    //     headers_obj.set(cx, key.as_str(), value_str)?;
-    // }
-    for (key, values) in &response.headers {
-        // 借用 HashMap，避免所有权转移
-        let js_value: Handle<JsValue> = if values.len() == 1 {
-            // 单值：直接转为字符串
-            cx.string(&values[0]).upcast()
-        } else {
-            // 多值：转为 JS 数组
-            let js_array = cx.empty_array();
-            for (i, value) in values.iter().enumerate() {
-                let js_str = cx.string(value);
-                js_array.set(cx, i as u32, js_str)?;
+    Ok(cx.undefined())
+}
+
+// Forget an abort token once its request no longer needs it
+fn abort_token_release(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let token_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(&mut cx, token_obj)?;
+
+    remove_token(id);
+
+    Ok(cx.undefined())
+}
+
+// Look up the cookie jar passed as first argument
+fn cookie_jar_arg(cx: &mut FunctionContext) -> NeonResult<Arc<CookieJar>> {
+    let jar_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(cx, jar_obj)?;
+
+    match get_jar(id) {
+        Some(jar) => Ok(jar),
+        None => cx.throw_error("Cookie jar not found"),
+    }
+}
+
+// Create a cookie jar that requests, clients and WebSockets can share
+fn cookie_jar_create(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let id = store_jar(Arc::new(CookieJar::new()));
+
+    let obj = cx.empty_object();
+    let id_num = cx.number(id as f64);
+    obj.set(&mut cx, "_id", id_num)?;
+    Ok(obj)
+}
+
+// Drop a cookie jar (clients using it keep their reference)
+fn cookie_jar_close(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let jar_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(&mut cx, jar_obj)?;
+
+    remove_jar(id);
+
+    Ok(cx.undefined())
+}
+
+// Cookies that would be sent to a URL, or every cookie without one
+fn cookie_jar_get(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let jar = cookie_jar_arg(&mut cx)?;
+
+    let cookies = match cx.argument_opt(1) {
+        Some(value) if !value.is_a::<JsUndefined, _>(&mut cx) => {
+            let url = value
+                .downcast_or_throw::<JsString, _>(&mut cx)?
+                .value(&mut cx);
+            let url = match Url::parse(&url) {
+                Ok(url) => url,
+                Err(e) => return cx.throw_type_error(format!("Invalid URL {}: {}", url, e)),
+            };
+            jar.cookies_for(&url)
+        }
+        _ => jar.all(),
+    };
+
+    let arr = cx.empty_array();
+    for (i, cookie) in cookies.iter().enumerate() {
+        let cookie_obj = stored_cookie_to_js_object(&mut cx, cookie)?;
+        arr.set(&mut cx, i as u32, cookie_obj)?;
+    }
+    Ok(arr)
+}
+
+// Store a Set-Cookie header value as if it came from a response for URL
+fn cookie_jar_set(mut cx: FunctionContext) -> JsResult<JsBoolean> {
+    let jar = cookie_jar_arg(&mut cx)?;
+    let url = cx.argument::<JsString>(1)?.value(&mut cx);
+    let set_cookie = cx.argument::<JsString>(2)?.value(&mut cx);
+
+    let url = match Url::parse(&url) {
+        Ok(url) => url,
+        Err(e) => return cx.throw_type_error(format!("Invalid URL {}: {}", url, e)),
+    };
+
+    let cookie = match Cookie::parse(&set_cookie) {
+        Some(cookie) => cookie,
+        None => return cx.throw_type_error(format!("Invalid cookie: {}", set_cookie)),
+    };
+
+    let stored = jar.store(&url, &cookie);
+    Ok(cx.boolean(stored))
+}
+
+// Delete cookies by name, optionally limited to a domain and path
+fn cookie_jar_delete(mut cx: FunctionContext) -> JsResult<JsNumber> {
+    let jar = cookie_jar_arg(&mut cx)?;
+    let name = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let domain = cx
+        .argument_opt(2)
+        .and_then(|v| v.downcast::<JsString, _>(&mut cx).ok())
+        .map(|v| v.value(&mut cx));
+    let path = cx
+        .argument_opt(3)
+        .and_then(|v| v.downcast::<JsString, _>(&mut cx).ok())
+        .map(|v| v.value(&mut cx));
+
+    let removed = jar.delete(&name, domain.as_deref(), path.as_deref());
+    Ok(cx.number(removed as f64))
+}
+
+// Remove every cookie
+fn cookie_jar_clear(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let jar = cookie_jar_arg(&mut cx)?;
+    jar.clear();
+    Ok(cx.undefined())
+}
+
+// Serialize a cookie jar ('json' or 'netscape')
+fn cookie_jar_export(mut cx: FunctionContext) -> JsResult<JsString> {
+    let jar = cookie_jar_arg(&mut cx)?;
+    let format = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    let output = match format.as_str() {
+        "json" => match jar.to_json() {
+            Ok(json) => json,
+            Err(e) => return throw_request_error(&mut cx, Error::from_anyhow(&e)),
+        },
+        "netscape" => jar.to_netscape(),
+        other => {
+            return cx.throw_type_error(format!(
+                "Invalid cookie format: {} (expected 'json' or 'netscape')",
+                other
+            ))
+        }
+    };
+
+    Ok(cx.string(output))
+}
+
+// Add cookies serialized as 'json' or 'netscape' to a cookie jar
+fn cookie_jar_import(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let jar = cookie_jar_arg(&mut cx)?;
+    let format = cx.argument::<JsString>(1)?.value(&mut cx);
+    let data = cx.argument::<JsString>(2)?.value(&mut cx);
+
+    let result = match format.as_str() {
+        "json" => jar.load_json(&data),
+        "netscape" => jar.load_netscape(&data),
+        other => {
+            return cx.throw_type_error(format!(
+                "Invalid cookie format: {} (expected 'json' or 'netscape')",
+                other
+            ))
+        }
+    };
+
+    if let Err(e) = result {
+        return throw_request_error(&mut cx, Error::from_anyhow(&e));
+    }
+
+    Ok(cx.undefined())
+}
+
+// Look up the proxy pool passed as first argument
+fn proxy_pool_arg(cx: &mut FunctionContext) -> NeonResult<Arc<ProxyPool>> {
+    let pool_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(cx, pool_obj)?;
+
+    match get_pool(id) {
+        Some(pool) => Ok(pool),
+        None => cx.throw_error("Proxy pool not found (was it closed?)"),
+    }
+}
+
+// Create a proxy pool: (urls, { strategy?, maxFailures?, cooldown? })
+fn proxy_pool_create(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let urls_arr = cx.argument::<JsArray>(0)?;
+    let options_obj = cx.argument::<JsObject>(1)?;
+
+    let mut urls = Vec::new();
+    for value in urls_arr.to_vec(&mut cx)? {
+        let url = value.downcast_or_throw::<JsString, _>(&mut cx)?;
+        urls.push(url.value(&mut cx));
+    }
+
+    let mut options = PoolOptions::default();
+
+    if let Some(name) = options_obj
+        .get_opt(&mut cx, "strategy")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
+        .map(|v| v.value(&mut cx))
+    {
+        options.strategy = match RotationStrategy::parse(&name) {
+            Some(strategy) => strategy,
+            None => {
+                return cx.throw_type_error(format!(
+                    "Invalid proxy strategy: {} (expected 'round-robin', 'random', 'sticky-host' or 'least-failures')",
+                    name
+                ))
             }
-            js_array.upcast()
         };
-        headers_obj.set(cx, key.as_str(), js_value)?;
     }
-    obj.set(cx, "headers", headers_obj)?;
 
-    // Cookies
-    let cookies_obj = cx.empty_object();
-    for (key, value) in response.cookies {
-        let value_str = cx.string(&value);
-        cookies_obj.set(cx, key.as_str(), value_str)?;
+    if let Some(max_failures) = options_obj
+        .get_opt(&mut cx, "maxFailures")?
+        .and_then(|v: Handle<JsValue>| v.downcast::<JsNumber, _>(&mut cx).ok())
+        .map(|v| v.value(&mut cx))
+    {
+        let max_failures = js_integer(&mut cx, "maxFailures", max_failures, 1, u32::MAX.into())?;
+        options.max_failures = max_failures as u32;
+    }
+
+    if let Some(cooldown) = js_timeout(&mut cx, options_obj, "cooldown")? {
+        options.cooldown_ms = cooldown;
     }
-    obj.set(cx, "cookies", cookies_obj)?;
 
-    // Body
-    let body = cx.string(&response.body);
-    obj.set(cx, "body", body)?;
+    let pool = match ProxyPool::new(&urls, options) {
+        Ok(pool) => pool,
+        Err(e) => return throw_request_error(&mut cx, Error::from_anyhow(&e)),
+    };
+
+    let id = store_pool(Arc::new(pool));
 
+    let obj = cx.empty_object();
+    let id_num = cx.number(id as f64);
+    obj.set(&mut cx, "_id", id_num)?;
     Ok(obj)
 }
 
-// Main request function exported to Node.js
-fn request(mut cx: FunctionContext) -> JsResult<JsPromise> {
-    // Get the options object
-    let options_obj = cx.argument::<JsObject>(0)?;
+// Health of every proxy of a pool
+fn proxy_pool_stats(mut cx: FunctionContext) -> JsResult<JsArray> {
+    let pool = proxy_pool_arg(&mut cx)?;
 
-    // Convert JS object to Rust struct
-    let options = js_object_to_request_options(&mut cx, options_obj)?;
+    let js_array = cx.empty_array();
+    for (i, stats) in pool.stats().iter().enumerate() {
+        let obj = cx.empty_object();
 
-    // Create a promise
-    let channel = cx.channel();
-    let (deferred, promise) = cx.promise();
+        let url = cx.string(&stats.url);
+        obj.set(&mut cx, "url", url)?;
 
-    // Create a new Tokio runtime for this request
-    std::thread::spawn(move || {
-        let rt = tokio::runtime::Runtime::new().expect("Failed to create Tokio runtime");
+        let available = cx.boolean(stats.available);
+        obj.set(&mut cx, "available", available)?;
 
-        // Make the request
-        let result = rt.block_on(make_request(options));
+        let consecutive_failures = cx.number(stats.consecutive_failures as f64);
+        obj.set(&mut cx, "consecutiveFailures", consecutive_failures)?;
 
-        // Send result back to JS
-        deferred.settle_with(&channel, move |mut cx| {
-            match result {
-                Ok(response) => response_to_js_object(&mut cx, response),
-                Err(e) => {
-                    // Format error with full chain for better debugging
-                    let error_msg = format!("{:#}", e);
-                    cx.throw_error(error_msg)
-                }
-            }
-        });
-    });
+        let failures = cx.number(stats.failures as f64);
+        obj.set(&mut cx, "failures", failures)?;
 
-    Ok(promise)
+        let successes = cx.number(stats.successes as f64);
+        obj.set(&mut cx, "successes", successes)?;
+
+        if let Some(remaining) = stats.cooldown_remaining_ms {
+            let remaining = cx.number(remaining as f64);
+            obj.set(&mut cx, "cooldownRemaining", remaining)?;
+        }
+
+        js_array.set(&mut cx, i as u32, obj)?;
+    }
+
+    Ok(js_array)
+}
+
+// Drop a proxy pool (clients created with it keep their reference)
+fn proxy_pool_close(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let pool_obj = cx.argument::<JsObject>(0)?;
+    let id = handle_id(&mut cx, pool_obj)?;
+
+    remove_pool(id);
+
+    Ok(cx.undefined())
 }
 
-// Get list of available browser profiles
+// Get list of available browser profiles, custom ones last
 fn get_profiles(mut cx: FunctionContext) -> JsResult<JsArray> {
-    let profiles = vec![
-        // Chrome
-        "chrome_100",
-        "chrome_101",
-        "chrome_104",
-        "chrome_105",
-        "chrome_106",
-        "chrome_107",
-        "chrome_108",
-        "chrome_109",
-        "chrome_110",
-        "chrome_114",
-        "chrome_116",
-        "chrome_117",
-        "chrome_118",
-        "chrome_119",
-        "chrome_120",
-        "chrome_123",
-        "chrome_124",
-        "chrome_126",
-        "chrome_127",
-        "chrome_128",
-        "chrome_129",
-        "chrome_130",
-        "chrome_131",
-        "chrome_132",
-        "chrome_133",
-        "chrome_134",
-        "chrome_135",
-        "chrome_136",
-        "chrome_137",
-        "chrome_138",
-        "chrome_139",
-        "chrome_140",
-        "chrome_141",
-        "chrome_142",
-        "chrome_143",
-        // Edge
-        "edge_101",
-        "edge_122",
-        "edge_127",
-        "edge_131",
-        "edge_134",
-        "edge_135",
-        "edge_136",
-        "edge_137",
-        "edge_138",
-        "edge_139",
-        "edge_140",
-        "edge_141",
-        "edge_142",
-        // Safari
-        "safari_ios_17_2",
-        "safari_ios_17_4_1",
-        "safari_ios_16_5",
-        "safari_15_3",
-        "safari_15_5",
-        "safari_15_6_1",
-        "safari_16",
-        "safari_16_5",
-        "safari_17_0",
-        "safari_17_2_1",
-        "safari_17_4_1",
-        "safari_17_5",
-        "safari_18",
-        "safari_ipad_18",
-        "safari_18_2",
-        "safari_ios_18_1_1",
-        "safari_18_3",
-        "safari_18_3_1",
-        "safari_18_5",
-        "safari_26",
-        "safari_26_1",
-        "safari_26_2",
-        "safari_ipad_26",
-        "safari_ipad_26_2",
-        "safari_ios_26",
-        "safari_ios_26_2",
-        // Firefox
-        "firefox_109",
-        "firefox_117",
-        "firefox_128",
-        "firefox_133",
-        "firefox_135",
-        "firefox_private_135",
-        "firefox_android_135",
-        "firefox_136",
-        "firefox_private_136",
-        "firefox_139",
-        "firefox_142",
-        "firefox_143",
-        "firefox_144",
-        "firefox_145",
-        "firefox_146",
-        // Opera
-        "opera_116",
-        "opera_117",
-        "opera_118",
-        "opera_119",
-        // OkHttp
-        "okhttp_3_9",
-        "okhttp_3_11",
-        "okhttp_3_13",
-        "okhttp_3_14",
-        "okhttp_4_9",
-        "okhttp_4_10",
-        "okhttp_4_12",
-        "okhttp_5",
-    ];
+    let custom = custom_profile_names();
+    let profiles = builtin_profile_names().chain(custom.iter().map(String::as_str));
 
     let js_array = cx.empty_array();
 
-    for (i, profile) in profiles.iter().enumerate() {
-        let js_string = cx.string(*profile);
+    for (i, profile) in profiles.enumerate() {
+        let js_string = cx.string(profile);
         js_array.set(&mut cx, i as u32, js_string)?;
     }
 
     Ok(js_array)
 }
 
+// Describe a profile by name (unknown names throw)
+fn describe_profile(mut cx: FunctionContext) -> JsResult<JsObject> {
+    let name = cx.argument::<JsString>(0)?.value(&mut cx);
+
+    let info = match profile::describe_profile(&name) {
+        Ok(info) => info,
+        Err(e) => return throw_request_error(&mut cx, Error::from_anyhow(&e)),
+    };
+
+    let obj = cx.empty_object();
+
+    let name = cx.string(&info.name);
+    obj.set(&mut cx, "name", name)?;
+
+    let family = cx.string(&info.family);
+    obj.set(&mut cx, "family", family)?;
+
+    if let Some(version) = &info.version {
+        let version = cx.string(version);
+        obj.set(&mut cx, "version", version)?;
+    }
+
+    if let Some(platform) = info.platform {
+        let platform = cx.string(platform);
+        obj.set(&mut cx, "platform", platform)?;
+    }
+
+    let private = cx.boolean(info.private);
+    obj.set(&mut cx, "private", private)?;
+
+    let http2 = cx.boolean(info.http2);
+    obj.set(&mut cx, "http2", http2)?;
+
+    if let Some(user_agent) = &info.user_agent {
+        let user_agent = cx.string(user_agent);
+        obj.set(&mut cx, "userAgent", user_agent)?;
+    }
+
+    let custom = cx.boolean(info.custom);
+    obj.set(&mut cx, "custom", custom)?;
+
+    let ja3 = cx.string(&info.fingerprint.ja3);
+    obj.set(&mut cx, "ja3", ja3)?;
+
+    let ja3_hash = cx.string(&info.fingerprint.ja3_hash);
+    obj.set(&mut cx, "ja3Hash", ja3_hash)?;
+
+    let ja4 = cx.string(&info.fingerprint.ja4);
+    obj.set(&mut cx, "ja4", ja4)?;
+
+    if let Some(akamai) = &info.fingerprint.akamai {
+        let akamai = cx.string(akamai);
+        obj.set(&mut cx, "akamai", akamai)?;
+    }
+
+    Ok(obj)
+}
+
+// Reseed random profile selection: (seed?), reseeded from the OS without one
+fn seed_profile_selection(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let seed = match cx.argument_opt(0) {
+        Some(value) if !value.is_a::<JsUndefined, _>(&mut cx) => {
+            let seed = value
+                .downcast_or_throw::<JsNumber, _>(&mut cx)?
+                .value(&mut cx);
+            Some(js_integer(&mut cx, "seed", seed, 0, MAX_SAFE_INTEGER)?)
+        }
+        _ => None,
+    };
+
+    seed_selection(seed);
+    Ok(cx.undefined())
+}
+
+// Register a custom profile from its JSON spec: (name, specJson)
+fn define_profile(mut cx: FunctionContext) -> JsResult<JsUndefined> {
+    let name = cx.argument::<JsString>(0)?.value(&mut cx);
+    let spec = cx.argument::<JsString>(1)?.value(&mut cx);
+
+    if name.is_empty() {
+        let error = Error::new(ErrorKind::InvalidRequest, "Profile name must not be empty");
+        return throw_request_error(&mut cx, error);
+    }
+    if let Err(e) = profile::define_profile(&name, &spec) {
+        let error = Error::from_anyhow(&e).or_kind(ErrorKind::InvalidRequest);
+        return throw_request_error(&mut cx, error);
+    }
+
+    Ok(cx.undefined())
+}
+
 // WebSocket connection function
 fn websocket_connect(mut cx: FunctionContext) -> JsResult<JsPromise> {
     // Get the options object
@@ -422,46 +1901,45 @@ fn websocket_connect(mut cx: FunctionContext) -> JsResult<JsPromise> {
     let url = url.value(&mut cx);
 
     // Get browser (optional, defaults to chrome_137)
-    let browser_str = options_obj
-        .get_opt(&mut cx, "browser")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
-        .map(|v| v.value(&mut cx))
-        .unwrap_or_else(|| "chrome_137".to_string());
+    let emulation = js_profile(&mut cx, options_obj)?;
 
-    let emulation = parse_emulation(&browser_str);
+    // Get headers (optional), how they are ordered against the profile's
+    // and changes to the profile's own
+    let headers = js_headers(&mut cx, options_obj, "headers")?;
+    let header_order = js_header_order(&mut cx, options_obj)?;
+    let profile_headers = js_profile_headers(&mut cx, options_obj)?;
 
-    // Get headers (optional)
-    let mut headers = HashMap::new();
-    if let Ok(Some(headers_obj)) = options_obj.get_opt::<JsObject, _, _>(&mut cx, "headers") {
-        let keys = headers_obj.get_own_property_names(&mut cx)?;
-        let keys_vec = keys.to_vec(&mut cx)?;
+    // Get proxy settings or proxy pool (optional)
+    let (proxy, proxy_pool) = js_proxy(&mut cx, options_obj)?;
 
-        for key_val in keys_vec {
-            if let Ok(key_str) = key_val.downcast::<JsString, _>(&mut cx) {
-                let key = key_str.value(&mut cx);
-                if let Ok(value) = headers_obj.get::<JsString, _, _>(&mut cx, key.as_str()) {
-                    headers.insert(key, value.value(&mut cx));
-                }
-            }
-        }
-    }
+    // Get local binding and address family preference (optional)
+    let network = js_network(&mut cx, options_obj)?;
 
-    // Get proxy (optional)
-    let proxy = options_obj
-        .get_opt(&mut cx, "proxy")?
-        .and_then(|v: Handle<JsValue>| v.downcast::<JsString, _>(&mut cx).ok())
-        .map(|v| v.value(&mut cx));
+    // Get trusted roots, client certificate and verification settings (optional)
+    let tls = js_tls(&mut cx, options_obj)?;
+
+    // Get cookie jar (optional)
+    let cookie_jar = js_cookie_jar(&mut cx, options_obj)?;
 
     // Get callbacks
     let on_message: Handle<JsFunction> = options_obj.get(&mut cx, "onMessage")?;
     let on_close_opt = options_obj.get_opt::<JsFunction, _, _>(&mut cx, "onClose")?;
     let on_error_opt = options_obj.get_opt::<JsFunction, _, _>(&mut cx, "onError")?;
 
+    // Kept for error reporting, options are moved into the connect task
+    let error_url = url.clone();
+
     let options = WebSocketOptions {
         url,
         emulation,
         headers,
+        header_order,
+        profile_headers,
         proxy,
+        proxy_pool,
+        network,
+        tls,
+        cookie_jar,
     };
 
     // Create a promise
@@ -556,17 +2034,27 @@ fn websocket_connect(mut cx: FunctionContext) -> JsResult<JsPromise> {
         deferred.settle_with(&channel, move |mut cx| {
             match result {
                 Ok(connection) => {
+                    let proxy = connection.proxy.clone();
+
                     // Store connection and get ID
                     let id = store_connection(connection);
 
                     let obj = cx.empty_object();
                     let id_num = cx.number(id as f64);
                     obj.set(&mut cx, "_id", id_num)?;
+
+                    if let Some(proxy) = proxy {
+                        let proxy = cx.string(proxy);
+                        obj.set(&mut cx, "proxy", proxy)?;
+                    }
+
                     Ok(obj)
                 }
                 Err(e) => {
-                    let error_msg = format!("{:#}", e);
-                    cx.throw_error(error_msg)
+                    let error = Error::from_anyhow(&e)
+                        .or_kind(ErrorKind::WebSocket)
+                        .with_request("GET", error_url);
+                    throw_request_error(&mut cx, error)
                 }
             }
         });
@@ -616,8 +2104,8 @@ fn websocket_send(mut cx: FunctionContext) -> JsResult<JsPromise> {
         deferred.settle_with(&channel, move |mut cx| match result {
             Ok(()) => Ok(cx.undefined()),
             Err(e) => {
-                let error_msg = format!("{:#}", e);
-                cx.throw_error(error_msg)
+                let error = Error::from_anyhow(&e).or_kind(ErrorKind::WebSocket);
+                throw_request_error(&mut cx, error)
             }
         });
     });
@@ -656,8 +2144,8 @@ fn websocket_close(mut cx: FunctionContext) -> JsResult<JsPromise> {
         deferred.settle_with(&channel, move |mut cx| match result {
             Ok(()) => Ok(cx.undefined()),
             Err(e) => {
-                let error_msg = format!("{:#}", e);
-                cx.throw_error(error_msg)
+                let error = Error::from_anyhow(&e).or_kind(ErrorKind::WebSocket);
+                throw_request_error(&mut cx, error)
             }
         });
     });
@@ -668,8 +2156,35 @@ fn websocket_close(mut cx: FunctionContext) -> JsResult<JsPromise> {
 // Module initialization
 #[neon::main]
 fn main(mut cx: ModuleContext) -> NeonResult<()> {
+    cx.export_function("init", init)?;
     cx.export_function("request", request)?;
+    cx.export_function("createClient", create_client)?;
+    cx.export_function("clientRequest", client_request)?;
+    cx.export_function("clientClose", client_close)?;
+    cx.export_function("bodySinkCreate", body_sink_create)?;
+    cx.export_function("bodySinkWrite", body_sink_write)?;
+    cx.export_function("bodySinkFinish", body_sink_finish)?;
+    cx.export_function("bodySinkAbort", body_sink_abort)?;
+    cx.export_function("streamRead", stream_read)?;
+    cx.export_function("streamClose", stream_close)?;
+    cx.export_function("abortTokenCreate", abort_token_create)?;
+    cx.export_function("abortTokenAbort", abort_token_abort)?;
+    cx.export_function("abortTokenRelease", abort_token_release)?;
+    cx.export_function("cookieJarCreate", cookie_jar_create)?;
+    cx.export_function("cookieJarClose", cookie_jar_close)?;
+    cx.export_function("cookieJarGet", cookie_jar_get)?;
+    cx.export_function("cookieJarSet", cookie_jar_set)?;
+    cx.export_function("cookieJarDelete", cookie_jar_delete)?;
+    cx.export_function("cookieJarClear", cookie_jar_clear)?;
+    cx.export_function("cookieJarExport", cookie_jar_export)?;
+    cx.export_function("cookieJarImport", cookie_jar_import)?;
+    cx.export_function("proxyPoolCreate", proxy_pool_create)?;
+    cx.export_function("proxyPoolStats", proxy_pool_stats)?;
+    cx.export_function("proxyPoolClose", proxy_pool_close)?;
     cx.export_function("getProfiles", get_profiles)?;
+    cx.export_function("defineProfile", define_profile)?;
+    cx.export_function("describeProfile", describe_profile)?;
+    cx.export_function("seedProfileSelection", seed_profile_selection)?;
     cx.export_function("websocketConnect", websocket_connect)?;
     cx.export_function("websocketSend", websocket_send)?;
     cx.export_function("websocketClose", websocket_close)?;
@@ -0,0 +1,103 @@
+use anyhow::{Context, Result};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use crate::dns::{DnsBackend, DnsOptions, DnsResolver};
+use crate::error::invalid;
+use crate::profile::Profile;
+use crate::proxy::{ProxyConfig, ProxyPool};
+use crate::tls::TlsOptions;
+
+/// Where outgoing connections leave from and how hosts are resolved
+#[derive(Debug, Clone, Default)]
+pub struct NetworkOptions {
+    /// Local IP address to bind outgoing connections to
+    pub local_address: Option<IpAddr>,
+    /// Network interface to bind outgoing connections to (e.g. "eth1")
+    pub interface: Option<String>,
+    pub dns: DnsOptions,
+}
+
+impl NetworkOptions {
+    /// Apply the binding and resolver settings to a client builder; DNS-over-HTTPS
+    /// queries go out with `profile` and `tls`, and through `proxy`, like the requests
+    /// they serve
+    pub fn configure(
+        &self,
+        builder: wreq::ClientBuilder,
+        profile: &Profile,
+        tls: &TlsOptions,
+        proxy: Option<&ProxyConfig>,
+        proxy_pool: Option<&ProxyPool>,
+    ) -> Result<wreq::ClientBuilder> {
+        let mut builder = self.bind(builder)?;
+
+        // Queries can't follow a pool's per-request picks, and going direct would leak them
+        if matches!(self.dns.backend, DnsBackend::Doh { .. }) && proxy_pool.is_some() {
+            return Err(invalid(
+                "The 'doh' DNS resolver can't be combined with a proxy pool (use a proxy instead)",
+            ));
+        }
+
+        if !self.dns.is_default() {
+            let resolver = DnsResolver::new(
+                &self.dns,
+                || {
+                    let builder = wreq::Client::builder()
+                        .emulation(profile.emulation())
+                        .no_proxy();
+                    tls.configure(self.bind(builder)?)?
+                        .build()
+                        .context("Failed to build DNS-over-HTTPS client")
+                },
+                proxy,
+            )?;
+            builder = builder.dns_resolver(Arc::new(resolver));
+        }
+
+        Ok(builder)
+    }
+
+    fn bind(&self, mut builder: wreq::ClientBuilder) -> Result<wreq::ClientBuilder> {
+        if let Some(local_address) = self.local_address {
+            builder = builder.local_address(local_address);
+        }
+
+        if let Some(interface) = &self.interface {
+            builder = bind_interface(builder, interface)?;
+        }
+
+        Ok(builder)
+    }
+}
+
+#[cfg(any(
+    target_os = "android",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "visionos",
+))]
+fn bind_interface(builder: wreq::ClientBuilder, interface: &str) -> Result<wreq::ClientBuilder> {
+    Ok(builder.interface(interface.to_string()))
+}
+
+#[cfg(not(any(
+    target_os = "android",
+    target_os = "fuchsia",
+    target_os = "linux",
+    target_os = "macos",
+    target_os = "ios",
+    target_os = "tvos",
+    target_os = "watchos",
+    target_os = "visionos",
+)))]
+fn bind_interface(_builder: wreq::ClientBuilder, interface: &str) -> Result<wreq::ClientBuilder> {
+    Err(invalid(format!(
+        "Binding to interface {} is not supported on this platform (use localAddress)",
+        interface
+    )))
+}
@@ -0,0 +1,987 @@
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex as StdMutex};
+use wreq::header::{HeaderMap, HeaderName, HeaderValue, OrigHeaderMap, USER_AGENT};
+use wreq::http2::{
+    Http2Options, PseudoId, PseudoOrder, SettingId, SettingsOrder, StreamDependency, StreamId,
+};
+use wreq::tls::{
+    AlpnProtocol, CertificateCompressionAlgorithm, ExtensionType, TlsOptions, TlsVersion,
+};
+use wreq::EmulationFactory;
+use wreq_util::{Emulation, EmulationOS, EmulationOption};
+
+use crate::error::invalid;
+use crate::fingerprint::{fingerprint, Fingerprint};
+
+// Profiles registered with `defineProfile`, by name
+static CUSTOM_PROFILES: Lazy<StdMutex<HashMap<String, Arc<CustomProfile>>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+// Random source of profile selection, seedable for reproducible runs
+static SELECTION_RNG: Lazy<StdMutex<StdRng>> = Lazy::new(|| StdMutex::new(StdRng::from_os_rng()));
+
+/// Browser fingerprint a client is built with
+#[derive(Debug, Clone)]
+pub enum Profile {
+    /// One of the profiles shipped with wreq-util
+    Builtin {
+        name: &'static str,
+        emulation: Emulation,
+        /// Operating system the User-Agent and client hints claim (the profile's own without it)
+        os: Option<EmulationOS>,
+    },
+    /// A profile registered with `defineProfile`
+    Custom(Arc<CustomProfile>),
+}
+
+/// A profile built from a `defineProfile` spec
+#[derive(Debug)]
+pub struct CustomProfile {
+    name: String,
+    emulation: wreq::Emulation,
+}
+
+impl Default for Profile {
+    /// Chrome 137, used when no `browser` is given
+    fn default() -> Self {
+        Profile::Builtin {
+            name: "chrome_137",
+            emulation: Emulation::Chrome137,
+            os: None,
+        }
+    }
+}
+
+impl Profile {
+    /// Name the profile is known by, reported back on responses
+    pub fn name(&self) -> &str {
+        match self {
+            Profile::Builtin { name, .. } => name,
+            Profile::Custom(custom) => &custom.name,
+        }
+    }
+
+    /// The wreq emulation (TLS, HTTP/2 and default headers) to build a client with
+    pub fn emulation(&self) -> wreq::Emulation {
+        match self {
+            Profile::Builtin {
+                emulation,
+                os: None,
+                ..
+            } => emulation.emulation(),
+            Profile::Builtin {
+                emulation,
+                os: Some(os),
+                ..
+            } => EmulationOption::builder()
+                .emulation(*emulation)
+                .emulation_os(*os)
+                .build()
+                .emulation(),
+            Profile::Custom(custom) => custom.emulation.clone(),
+        }
+    }
+
+    /// Pair a built-in profile with another operating system
+    pub fn with_os(self, os: EmulationOS) -> Result<Self> {
+        match self {
+            Profile::Builtin {
+                name, emulation, ..
+            } => Ok(Profile::Builtin {
+                name,
+                emulation,
+                os: Some(os),
+            }),
+            Profile::Custom(custom) => Err(invalid(format!(
+                "os can't be combined with the custom profile {}; set its headers instead",
+                custom.name
+            ))),
+        }
+    }
+}
+
+/// Built-in profiles by name, in the order `getProfiles()` lists them
+static BUILTIN_PROFILES: &[(&str, Emulation)] = &[
+    // Chrome
+    ("chrome_100", Emulation::Chrome100),
+    ("chrome_101", Emulation::Chrome101),
+    ("chrome_104", Emulation::Chrome104),
+    ("chrome_105", Emulation::Chrome105),
+    ("chrome_106", Emulation::Chrome106),
+    ("chrome_107", Emulation::Chrome107),
+    ("chrome_108", Emulation::Chrome108),
+    ("chrome_109", Emulation::Chrome109),
+    ("chrome_110", Emulation::Chrome110),
+    ("chrome_114", Emulation::Chrome114),
+    ("chrome_116", Emulation::Chrome116),
+    ("chrome_117", Emulation::Chrome117),
+    ("chrome_118", Emulation::Chrome118),
+    ("chrome_119", Emulation::Chrome119),
+    ("chrome_120", Emulation::Chrome120),
+    ("chrome_123", Emulation::Chrome123),
+    ("chrome_124", Emulation::Chrome124),
+    ("chrome_126", Emulation::Chrome126),
+    ("chrome_127", Emulation::Chrome127),
+    ("chrome_128", Emulation::Chrome128),
+    ("chrome_129", Emulation::Chrome129),
+    ("chrome_130", Emulation::Chrome130),
+    ("chrome_131", Emulation::Chrome131),
+    ("chrome_132", Emulation::Chrome132),
+    ("chrome_133", Emulation::Chrome133),
+    ("chrome_134", Emulation::Chrome134),
+    ("chrome_135", Emulation::Chrome135),
+    ("chrome_136", Emulation::Chrome136),
+    ("chrome_137", Emulation::Chrome137),
+    ("chrome_138", Emulation::Chrome138),
+    ("chrome_139", Emulation::Chrome139),
+    ("chrome_140", Emulation::Chrome140),
+    ("chrome_141", Emulation::Chrome141),
+    ("chrome_142", Emulation::Chrome142),
+    ("chrome_143", Emulation::Chrome143),
+    // Edge
+    ("edge_101", Emulation::Edge101),
+    ("edge_122", Emulation::Edge122),
+    ("edge_127", Emulation::Edge127),
+    ("edge_131", Emulation::Edge131),
+    ("edge_134", Emulation::Edge134),
+    ("edge_135", Emulation::Edge135),
+    ("edge_136", Emulation::Edge136),
+    ("edge_137", Emulation::Edge137),
+    ("edge_138", Emulation::Edge138),
+    ("edge_139", Emulation::Edge139),
+    ("edge_140", Emulation::Edge140),
+    ("edge_141", Emulation::Edge141),
+    ("edge_142", Emulation::Edge142),
+    // Safari
+    ("safari_ios_17_2", Emulation::SafariIos17_2),
+    ("safari_ios_17_4_1", Emulation::SafariIos17_4_1),
+    ("safari_ios_16_5", Emulation::SafariIos16_5),
+    ("safari_15_3", Emulation::Safari15_3),
+    ("safari_15_5", Emulation::Safari15_5),
+    ("safari_15_6_1", Emulation::Safari15_6_1),
+    ("safari_16", Emulation::Safari16),
+    ("safari_16_5", Emulation::Safari16_5),
+    ("safari_17_0", Emulation::Safari17_0),
+    ("safari_17_2_1", Emulation::Safari17_2_1),
+    ("safari_17_4_1", Emulation::Safari17_4_1),
+    ("safari_17_5", Emulation::Safari17_5),
+    ("safari_18", Emulation::Safari18),
+    ("safari_ipad_18", Emulation::SafariIPad18),
+    ("safari_18_2", Emulation::Safari18_2),
+    ("safari_ios_18_1_1", Emulation::SafariIos18_1_1),
+    ("safari_18_3", Emulation::Safari18_3),
+    ("safari_18_3_1", Emulation::Safari18_3_1),
+    ("safari_18_5", Emulation::Safari18_5),
+    ("safari_26", Emulation::Safari26),
+    ("safari_26_1", Emulation::Safari26_1),
+    ("safari_26_2", Emulation::Safari26_2),
+    ("safari_ipad_26", Emulation::SafariIPad26),
+    ("safari_ipad_26_2", Emulation::SafariIpad26_2),
+    ("safari_ios_26", Emulation::SafariIos26),
+    ("safari_ios_26_2", Emulation::SafariIos26_2),
+    // Firefox
+    ("firefox_109", Emulation::Firefox109),
+    ("firefox_117", Emulation::Firefox117),
+    ("firefox_128", Emulation::Firefox128),
+    ("firefox_133", Emulation::Firefox133),
+    ("firefox_135", Emulation::Firefox135),
+    ("firefox_private_135", Emulation::FirefoxPrivate135),
+    ("firefox_android_135", Emulation::FirefoxAndroid135),
+    ("firefox_136", Emulation::Firefox136),
+    ("firefox_private_136", Emulation::FirefoxPrivate136),
+    ("firefox_139", Emulation::Firefox139),
+    ("firefox_142", Emulation::Firefox142),
+    ("firefox_143", Emulation::Firefox143),
+    ("firefox_144", Emulation::Firefox144),
+    ("firefox_145", Emulation::Firefox145),
+    ("firefox_146", Emulation::Firefox146),
+    // Opera
+    ("opera_116", Emulation::Opera116),
+    ("opera_117", Emulation::Opera117),
+    ("opera_118", Emulation::Opera118),
+    ("opera_119", Emulation::Opera119),
+    // OkHttp
+    ("okhttp_3_9", Emulation::OkHttp3_9),
+    ("okhttp_3_11", Emulation::OkHttp3_11),
+    ("okhttp_3_13", Emulation::OkHttp3_13),
+    ("okhttp_3_14", Emulation::OkHttp3_14),
+    ("okhttp_4_9", Emulation::OkHttp4_9),
+    ("okhttp_4_10", Emulation::OkHttp4_10),
+    ("okhttp_4_12", Emulation::OkHttp4_12),
+    ("okhttp_5", Emulation::OkHttp5),
+];
+
+/// Names of the built-in profiles
+pub fn builtin_profile_names() -> impl Iterator<Item = &'static str> {
+    BUILTIN_PROFILES.iter().map(|(name, _)| *name)
+}
+
+fn builtin_profile(name: &str) -> Option<Profile> {
+    BUILTIN_PROFILES
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(name, emulation)| Profile::Builtin {
+            name,
+            emulation: *emulation,
+            os: None,
+        })
+}
+
+/// Look up a profile registered with `defineProfile`
+pub fn get_custom_profile(name: &str) -> Option<Profile> {
+    let profiles = CUSTOM_PROFILES.lock().unwrap();
+    profiles.get(name).cloned().map(Profile::Custom)
+}
+
+/// Resolve a browser name to its profile.
+///
+/// Unknown names are rejected with the closest matches in the message; with
+/// `lenient` they resolve to the nearest known version of the same browser
+/// instead (`chrome_139` to `chrome_138`, `Chrome-137` to `chrome_137`).
+pub fn resolve_profile(name: &str, lenient: bool) -> Result<Profile> {
+    if let Some(profile) = get_custom_profile(name) {
+        return Ok(profile);
+    }
+    if let Some(profile) = builtin_profile(name) {
+        return Ok(profile);
+    }
+
+    if lenient {
+        if let Some(profile) = nearest_version(name).and_then(builtin_profile) {
+            return Ok(profile);
+        }
+    }
+
+    let suggestions = suggestions(name);
+    let message = if suggestions.is_empty() {
+        format!(
+            "Unknown browser profile: {} (see getProfiles() for the available ones)",
+            name
+        )
+    } else {
+        format!(
+            "Unknown browser profile: {} (did you mean {}?)",
+            name,
+            suggestions.join(", ")
+        )
+    };
+
+    Err(invalid(message))
+}
+
+/// What a profile impersonates, for `describeProfile()`
+#[derive(Debug, Clone)]
+pub struct ProfileInfo {
+    pub name: String,
+    /// `chrome`, `edge`, `safari`, `firefox`, `opera`, `okhttp` or `custom`
+    pub family: String,
+    /// Browser version, e.g. `18.1.1` (none for custom profiles)
+    pub version: Option<String>,
+    /// `desktop`, `ios`, `ipad` or `android` (none for custom profiles)
+    pub platform: Option<&'static str>,
+    /// Private browsing mode
+    pub private: bool,
+    /// Whether `h2` is offered ahead of HTTP/1.1
+    pub http2: bool,
+    /// Default `User-Agent` header
+    pub user_agent: Option<String>,
+    pub custom: bool,
+    /// JA3, JA4 and Akamai HTTP/2 fingerprints of the profile's client
+    pub fingerprint: Fingerprint,
+}
+
+/// Describe a profile by its exact name
+pub fn describe_profile(name: &str) -> Result<ProfileInfo> {
+    let profile = resolve_profile(name, false)?;
+    let mut emulation = profile.emulation();
+    let user_agent = emulation
+        .headers_mut()
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let http2 = offers_http2(&mut emulation);
+    let fingerprint = fingerprint(&profile, http2)?;
+
+    if let Profile::Custom(_) = &profile {
+        return Ok(ProfileInfo {
+            name: name.to_string(),
+            family: "custom".to_string(),
+            version: None,
+            platform: None,
+            private: false,
+            http2,
+            user_agent,
+            custom: true,
+            fingerprint,
+        });
+    }
+
+    let builtin = BuiltinName::parse(name);
+
+    Ok(ProfileInfo {
+        name: name.to_string(),
+        version: (!builtin.version.is_empty()).then(|| {
+            builtin
+                .version
+                .iter()
+                .map(u32::to_string)
+                .collect::<Vec<_>>()
+                .join(".")
+        }),
+        family: builtin.family,
+        platform: Some(builtin.platform),
+        private: builtin.private,
+        http2,
+        user_agent,
+        custom: false,
+        fingerprint,
+    })
+}
+
+// Whether the ClientHello offers `h2` ahead of HTTP/1.1: the first ALPN protocol,
+// or BoringSSL's default (`h2`, `http/1.1`) for emulations that don't set any
+fn offers_http2(emulation: &mut wreq::Emulation) -> bool {
+    emulation
+        .tls_options_mut()
+        .as_ref()
+        .and_then(|tls| tls.alpn_protocols.as_deref())
+        .is_none_or(|alpn| alpn.first() == Some(&AlpnProtocol::HTTP2))
+}
+
+// What a built-in name spells out: `safari_ios_18_1_1`, `firefox_private_135`, `okhttp_4_12`
+struct BuiltinName {
+    family: String,
+    version: Vec<u32>,
+    platform: &'static str,
+    private: bool,
+}
+
+impl BuiltinName {
+    fn parse(name: &str) -> Self {
+        let (family, version) = split_name(name);
+        let mut parts = family.split('_');
+        let browser = parts.next().unwrap_or_default().to_string();
+        let variants: Vec<&str> = parts.collect();
+
+        let platform = if variants.contains(&"ios") {
+            "ios"
+        } else if variants.contains(&"ipad") {
+            "ipad"
+        } else if variants.contains(&"android") || browser == "okhttp" {
+            "android"
+        } else {
+            "desktop"
+        };
+
+        Self {
+            family: browser,
+            version,
+            platform,
+            private: variants.contains(&"private"),
+        }
+    }
+}
+
+/// Restricts which built-in profiles a random selection picks from
+#[derive(Debug, Clone, Default)]
+pub struct ProfileFilter {
+    pub family: Option<String>,
+    pub platform: Option<String>,
+    /// Lowest major version, inclusive
+    pub min_version: Option<u32>,
+    /// Highest major version, inclusive
+    pub max_version: Option<u32>,
+}
+
+impl ProfileFilter {
+    fn matches(&self, name: &str) -> bool {
+        let builtin = BuiltinName::parse(name);
+        let major = builtin.version.first().copied().unwrap_or(0);
+
+        self.family
+            .as_ref()
+            .is_none_or(|family| *family == builtin.family)
+            && self
+                .platform
+                .as_ref()
+                .is_none_or(|platform| platform == builtin.platform)
+            && self.min_version.is_none_or(|min| major >= min)
+            && self.max_version.is_none_or(|max| major <= max)
+    }
+
+    // Custom profiles have no family, platform or version to match a filter
+    fn allows_custom(&self) -> bool {
+        self.family.is_none()
+            && self.platform.is_none()
+            && self.min_version.is_none()
+            && self.max_version.is_none()
+    }
+}
+
+/// How `browser` picks a profile
+#[derive(Debug, Clone)]
+pub enum ProfileSelector {
+    /// A profile by name
+    Name(String),
+    /// A random built-in profile matching `filter`, picked by `weights` when
+    /// set: profile names or families (shared evenly by its profiles)
+    Random {
+        filter: ProfileFilter,
+        weights: Vec<(String, f64)>,
+    },
+}
+
+/// Reseed the RNG random selections draw from (`None` seeds from the OS)
+pub fn seed_selection(seed: Option<u64>) {
+    let mut rng = SELECTION_RNG.lock().unwrap();
+    *rng = match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    };
+}
+
+/// Operating system for `Profile::with_os`, by its `os` option name
+pub fn parse_os(name: &str) -> Option<EmulationOS> {
+    match name {
+        "windows" => Some(EmulationOS::Windows),
+        "macos" => Some(EmulationOS::MacOS),
+        "linux" => Some(EmulationOS::Linux),
+        "android" => Some(EmulationOS::Android),
+        "ios" => Some(EmulationOS::IOS),
+        _ => None,
+    }
+}
+
+/// Resolve a selector to a profile, drawing random ones from the shared RNG
+pub fn select_profile(selector: &ProfileSelector, lenient: bool) -> Result<Profile> {
+    let (filter, weights) = match selector {
+        ProfileSelector::Name(name) => return resolve_profile(name, lenient),
+        ProfileSelector::Random { filter, weights } => (filter, weights),
+    };
+
+    let candidates: Vec<&'static str> = builtin_profile_names()
+        .filter(|name| filter.matches(name))
+        .collect();
+
+    let mut weighted: Vec<(String, f64)> = Vec::new();
+    if weights.is_empty() {
+        weighted.extend(candidates.iter().map(|name| (name.to_string(), 1.0)));
+    }
+    for (key, weight) in weights {
+        if !weight.is_finite() || *weight < 0.0 {
+            return Err(invalid(format!("Invalid weight for {}: {}", key, weight)));
+        }
+
+        if candidates.contains(&key.as_str())
+            || (filter.allows_custom() && get_custom_profile(key).is_some())
+        {
+            weighted.push((key.clone(), *weight));
+            continue;
+        }
+
+        // A family shares its weight evenly between its selectable profiles
+        let family: Vec<&str> = candidates
+            .iter()
+            .copied()
+            .filter(|name| BuiltinName::parse(name).family == *key)
+            .collect();
+        if family.is_empty() {
+            return Err(invalid(format!(
+                "Weight key {} matches no selectable profile or family",
+                key
+            )));
+        }
+        let share = weight / family.len() as f64;
+        weighted.extend(family.into_iter().map(|name| (name.to_string(), share)));
+    }
+
+    let total: f64 = weighted.iter().map(|(_, weight)| weight).sum();
+    if weighted.is_empty() || total <= 0.0 {
+        return Err(invalid("No browser profile matches the selection".into()));
+    }
+
+    let mut point = SELECTION_RNG.lock().unwrap().random_range(0.0..total);
+    let mut chosen = &weighted[weighted.len() - 1].0;
+    for (name, weight) in &weighted {
+        if point < *weight {
+            chosen = name;
+            break;
+        }
+        point -= weight;
+    }
+
+    resolve_profile(chosen, false)
+}
+
+// Family and version of a profile name: `safari_ios_18_1_1` is ("safari_ios", [18, 1, 1]).
+// Case, `-`/`.` separators and a missing `_` before the version are tolerated.
+fn split_name(name: &str) -> (String, Vec<u32>) {
+    let mut normalized = String::with_capacity(name.len() + 1);
+    let mut previous = '_';
+    for c in name.to_lowercase().chars() {
+        let c = if matches!(c, '-' | '.' | ' ') { '_' } else { c };
+        if c.is_ascii_digit() && previous.is_ascii_alphabetic() {
+            normalized.push('_');
+        }
+        normalized.push(c);
+        previous = c;
+    }
+
+    let mut family = Vec::new();
+    let mut version = Vec::new();
+    for part in normalized.split('_').filter(|part| !part.is_empty()) {
+        match part.parse::<u32>() {
+            Ok(number) => version.push(number),
+            Err(_) => family.push(part),
+        }
+    }
+
+    (family.join("_"), version)
+}
+
+// Built-in profile of the same family closest in version (newest without a version)
+fn nearest_version(name: &str) -> Option<&'static str> {
+    let (family, version) = split_name(name);
+    let key = |version: &[u32]| {
+        (0..3).fold(0i64, |key, i| {
+            key * 1000 + i64::from(version.get(i).copied().unwrap_or(0))
+        })
+    };
+    let wanted = key(&version);
+
+    builtin_profile_names()
+        .map(|builtin| (builtin, split_name(builtin)))
+        .filter(|(_, (builtin_family, _))| *builtin_family == family)
+        .map(|(builtin, (_, builtin_version))| {
+            let distance = if version.is_empty() {
+                -key(&builtin_version)
+            } else {
+                (key(&builtin_version) - wanted).abs()
+            };
+            // Ties go to the newer version
+            (distance, -key(&builtin_version), builtin)
+        })
+        .min()
+        .map(|(_, _, builtin)| builtin)
+}
+
+// Up to three known names close to an unknown one
+fn suggestions(name: &str) -> Vec<String> {
+    let wanted = name.to_lowercase();
+    let mut scored: Vec<(usize, String)> = builtin_profile_names()
+        .map(str::to_string)
+        .chain(custom_profile_names())
+        .map(|known| (edit_distance(&wanted, &known), known))
+        .filter(|(distance, known)| *distance <= (known.len() / 4).max(2))
+        .collect();
+    scored.sort();
+
+    let mut suggestions: Vec<String> = nearest_version(name)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    for (_, known) in scored {
+        if suggestions.len() == 3 {
+            break;
+        }
+        if !suggestions.contains(&known) {
+            suggestions.push(known);
+        }
+    }
+    suggestions
+}
+
+// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Names of the registered custom profiles, sorted
+pub fn custom_profile_names() -> Vec<String> {
+    let profiles = CUSTOM_PROFILES.lock().unwrap();
+    let mut names: Vec<String> = profiles.keys().cloned().collect();
+    names.sort();
+    names
+}
+
+/// `browser` value picking a random built-in profile
+pub const RANDOM_PROFILE: &str = "random";
+
+/// Build a profile from its JSON description and register it under `name`,
+/// replacing an earlier definition
+pub fn define_profile(name: &str, spec: &str) -> Result<()> {
+    if builtin_profile(name).is_some() {
+        return Err(invalid(format!(
+            "Cannot redefine built-in profile: {}",
+            name
+        )));
+    }
+    // `browser: 'random'` never looks the name up, so such a profile couldn't be used
+    if name == RANDOM_PROFILE {
+        return Err(invalid(format!(
+            "{} is reserved for random profile selection",
+            RANDOM_PROFILE
+        )));
+    }
+
+    let spec: ProfileSpec =
+        serde_json::from_str(spec).map_err(|e| invalid(format!("Invalid profile spec: {}", e)))?;
+    let emulation = spec
+        .build()
+        .with_context(|| format!("Invalid profile spec for {}", name))?;
+
+    let mut profiles = CUSTOM_PROFILES.lock().unwrap();
+    profiles.insert(
+        name.to_string(),
+        Arc::new(CustomProfile {
+            name: name.to_string(),
+            emulation,
+        }),
+    );
+    Ok(())
+}
+
+/// JSON description of a custom profile
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ProfileSpec {
+    tls: Option<TlsSpec>,
+    http2: Option<Http2Spec>,
+    /// Ordered `[name, value]` pairs, or an object (kept in document order)
+    headers: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct TlsSpec {
+    /// OpenSSL cipher string, e.g. `TLS_AES_128_GCM_SHA256:ECDHE-ECDSA-AES128-GCM-SHA256`
+    cipher_list: Option<String>,
+    /// Supported groups, e.g. `X25519MLKEM768:X25519:P-256:P-384`
+    curves: Option<String>,
+    /// Signature algorithms, e.g. `ecdsa_secp256r1_sha256:rsa_pss_rsae_sha256`
+    sigalgs: Option<String>,
+    /// ALPN protocols in preference order (`h2`, `http/1.1`)
+    alpn: Option<Vec<String>>,
+    /// Fixed extension order by IANA extension ID
+    extension_order: Option<Vec<u16>>,
+    /// Shuffle the extension order on every handshake, like Chrome
+    permute_extensions: Option<bool>,
+    grease: Option<bool>,
+    ech_grease: Option<bool>,
+    pre_shared_key: Option<bool>,
+    ocsp_stapling: Option<bool>,
+    signed_cert_timestamps: Option<bool>,
+    /// `brotli`, `zlib` and/or `zstd`
+    cert_compression: Option<Vec<String>>,
+    /// `1.0`, `1.1`, `1.2` or `1.3`
+    min_version: Option<String>,
+    max_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct Http2Spec {
+    header_table_size: Option<u32>,
+    enable_push: Option<bool>,
+    max_concurrent_streams: Option<u32>,
+    initial_window_size: Option<u32>,
+    max_frame_size: Option<u32>,
+    max_header_list_size: Option<u32>,
+    /// Order of the SETTINGS frame entries, by the camelCase names above
+    settings_order: Option<Vec<String>>,
+    /// Connection window size announced with the first WINDOW_UPDATE
+    connection_window_size: Option<u32>,
+    /// e.g. `[":method", ":authority", ":scheme", ":path"]`
+    pseudo_header_order: Option<Vec<String>>,
+    /// Priority sent with the HEADERS frame
+    priority: Option<PrioritySpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct PrioritySpec {
+    #[serde(default)]
+    stream_dependency: u32,
+    #[serde(default = "default_weight")]
+    weight: u8,
+    #[serde(default)]
+    exclusive: bool,
+}
+
+fn default_weight() -> u8 {
+    255
+}
+
+impl ProfileSpec {
+    fn build(&self) -> Result<wreq::Emulation> {
+        let mut builder = wreq::Emulation::builder();
+
+        if let Some(tls) = &self.tls {
+            builder = builder.tls_options(tls.build()?);
+        }
+
+        if let Some(http2) = &self.http2 {
+            builder = builder.http2_options(http2.build()?);
+        }
+
+        if let Some(headers) = &self.headers {
+            let (headers, orig_headers) = spec_headers(headers)?;
+            builder = builder.headers(headers).orig_headers(orig_headers);
+        }
+
+        Ok(builder.build())
+    }
+}
+
+impl TlsSpec {
+    fn build(&self) -> Result<TlsOptions> {
+        let mut builder = TlsOptions::builder();
+
+        if let Some(cipher_list) = &self.cipher_list {
+            builder = builder.cipher_list(cipher_list.clone());
+        }
+        if let Some(curves) = &self.curves {
+            builder = builder.curves_list(curves.clone());
+        }
+        if let Some(sigalgs) = &self.sigalgs {
+            builder = builder.sigalgs_list(sigalgs.clone());
+        }
+        if let Some(alpn) = &self.alpn {
+            let protocols = alpn
+                .iter()
+                .map(|protocol| match protocol.as_str() {
+                    "h2" => Ok(AlpnProtocol::HTTP2),
+                    "http/1.1" => Ok(AlpnProtocol::HTTP1),
+                    other => Err(invalid(format!("Unsupported ALPN protocol: {}", other))),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            builder = builder.alpn_protocols(protocols);
+        }
+        if let Some(order) = &self.extension_order {
+            let order: Vec<ExtensionType> =
+                order.iter().map(|&id| ExtensionType::from(id)).collect();
+            builder = builder.extension_permutation(order);
+        }
+        if let Some(permute) = self.permute_extensions {
+            builder = builder.permute_extensions(permute);
+        }
+        if let Some(grease) = self.grease {
+            builder = builder.grease_enabled(grease);
+        }
+        if let Some(ech_grease) = self.ech_grease {
+            builder = builder.enable_ech_grease(ech_grease);
+        }
+        if let Some(pre_shared_key) = self.pre_shared_key {
+            builder = builder.pre_shared_key(pre_shared_key);
+        }
+        if let Some(ocsp_stapling) = self.ocsp_stapling {
+            builder = builder.enable_ocsp_stapling(ocsp_stapling);
+        }
+        if let Some(signed_cert_timestamps) = self.signed_cert_timestamps {
+            builder = builder.enable_signed_cert_timestamps(signed_cert_timestamps);
+        }
+        if let Some(algorithms) = &self.cert_compression {
+            let algorithms = algorithms
+                .iter()
+                .map(|algorithm| match algorithm.as_str() {
+                    "brotli" => Ok(CertificateCompressionAlgorithm::BROTLI),
+                    "zlib" => Ok(CertificateCompressionAlgorithm::ZLIB),
+                    "zstd" => Ok(CertificateCompressionAlgorithm::ZSTD),
+                    other => Err(invalid(format!(
+                        "Unsupported certificate compression: {}",
+                        other
+                    ))),
+                })
+                .collect::<Result<Vec<_>>>()?;
+            builder = builder.certificate_compression_algorithms(algorithms);
+        }
+        if let Some(version) = &self.min_version {
+            builder = builder.min_tls_version(tls_version(version)?);
+        }
+        if let Some(version) = &self.max_version {
+            builder = builder.max_tls_version(tls_version(version)?);
+        }
+
+        Ok(builder.build())
+    }
+}
+
+impl Http2Spec {
+    fn build(&self) -> Result<Http2Options> {
+        let mut builder = Http2Options::builder();
+
+        if let Some(size) = self.header_table_size {
+            builder = builder.header_table_size(size);
+        }
+        if let Some(enable_push) = self.enable_push {
+            builder = builder.enable_push(enable_push);
+        }
+        if let Some(max) = self.max_concurrent_streams {
+            builder = builder.max_concurrent_streams(max);
+        }
+        if let Some(size) = self.initial_window_size {
+            builder = builder.initial_window_size(size);
+        }
+        if let Some(size) = self.max_frame_size {
+            builder = builder.max_frame_size(size);
+        }
+        if let Some(size) = self.max_header_list_size {
+            builder = builder.max_header_list_size(size);
+        }
+        if let Some(size) = self.connection_window_size {
+            builder = builder.initial_connection_window_size(size);
+        }
+        if let Some(order) = &self.settings_order {
+            let order = order
+                .iter()
+                .map(|name| setting_id(name))
+                .collect::<Result<Vec<_>>>()?;
+            builder = builder.settings_order(SettingsOrder::builder().extend(order).build());
+        }
+        if let Some(order) = &self.pseudo_header_order {
+            let order = order
+                .iter()
+                .map(|name| pseudo_id(name))
+                .collect::<Result<Vec<_>>>()?;
+            builder = builder.headers_pseudo_order(PseudoOrder::builder().extend(order).build());
+        }
+        if let Some(priority) = &self.priority {
+            builder = builder.headers_stream_dependency(StreamDependency::new(
+                StreamId::from(priority.stream_dependency),
+                priority.weight,
+                priority.exclusive,
+            ));
+        }
+
+        Ok(builder.build())
+    }
+}
+
+fn tls_version(version: &str) -> Result<TlsVersion> {
+    match version {
+        "1.0" => Ok(TlsVersion::TLS_1_0),
+        "1.1" => Ok(TlsVersion::TLS_1_1),
+        "1.2" => Ok(TlsVersion::TLS_1_2),
+        "1.3" => Ok(TlsVersion::TLS_1_3),
+        other => Err(invalid(format!("Unsupported TLS version: {}", other))),
+    }
+}
+
+fn setting_id(name: &str) -> Result<SettingId> {
+    match name {
+        "headerTableSize" => Ok(SettingId::HeaderTableSize),
+        "enablePush" => Ok(SettingId::EnablePush),
+        "maxConcurrentStreams" => Ok(SettingId::MaxConcurrentStreams),
+        "initialWindowSize" => Ok(SettingId::InitialWindowSize),
+        "maxFrameSize" => Ok(SettingId::MaxFrameSize),
+        "maxHeaderListSize" => Ok(SettingId::MaxHeaderListSize),
+        "enableConnectProtocol" => Ok(SettingId::EnableConnectProtocol),
+        "noRfc7540Priorities" => Ok(SettingId::NoRfc7540Priorities),
+        other => Err(invalid(format!("Unknown HTTP/2 setting: {}", other))),
+    }
+}
+
+fn pseudo_id(name: &str) -> Result<PseudoId> {
+    match name {
+        ":method" => Ok(PseudoId::Method),
+        ":authority" => Ok(PseudoId::Authority),
+        ":scheme" => Ok(PseudoId::Scheme),
+        ":path" => Ok(PseudoId::Path),
+        ":protocol" => Ok(PseudoId::Protocol),
+        other => Err(invalid(format!("Unknown pseudo-header: {}", other))),
+    }
+}
+
+// Default headers in document order, with their casing kept for HTTP/1
+fn spec_headers(value: &Value) -> Result<(HeaderMap, OrigHeaderMap)> {
+    let mut pairs = Vec::new();
+
+    match value {
+        Value::Array(entries) => {
+            for entry in entries {
+                match entry.as_array().map(Vec::as_slice) {
+                    Some([Value::String(name), Value::String(value)]) => {
+                        pairs.push((name.clone(), value.clone()))
+                    }
+                    _ => {
+                        return Err(invalid(
+                            "Profile headers must be [name, value] pairs".into(),
+                        ))
+                    }
+                }
+            }
+        }
+        Value::Object(entries) => {
+            for (name, value) in entries {
+                let value = value.as_str().ok_or_else(|| {
+                    invalid(format!("Value of profile header {} must be a string", name))
+                })?;
+                pairs.push((name.clone(), value.to_string()));
+            }
+        }
+        _ => {
+            return Err(invalid(
+                "Profile headers must be an object or an array".into(),
+            ))
+        }
+    }
+
+    let mut headers = HeaderMap::new();
+    let mut orig_headers = OrigHeaderMap::new();
+    for (name, value) in pairs {
+        let header_name = HeaderName::from_bytes(name.as_bytes())
+            .map_err(|_| invalid(format!("Invalid header name: {}", name)))?;
+        let header_value = HeaderValue::from_str(&value)
+            .map_err(|_| invalid(format!("Invalid value for header {}", name)))?;
+        headers.append(header_name, header_value);
+        orig_headers.insert(name);
+    }
+
+    Ok((headers, orig_headers))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nearest_version_tolerates_spelling_and_prefers_newer() {
+        assert_eq!(nearest_version("Chrome-121"), Some("chrome_120"));
+        // 124 and 126 are as far from 125, the newer one wins
+        assert_eq!(nearest_version("chrome125"), Some("chrome_126"));
+        assert_eq!(nearest_version("chrome"), Some("chrome_143"));
+        assert_eq!(nearest_version("Safari 18.4"), Some("safari_18_3_1"));
+        assert_eq!(nearest_version("safari_ios_17_3"), Some("safari_ios_17_2"));
+        assert_eq!(nearest_version("netscape_4"), None);
+    }
+
+    #[test]
+    fn unknown_names_suggest_or_resolve_leniently() {
+        let error = resolve_profile("chrom_137", false).unwrap_err().to_string();
+        assert!(error.contains("did you mean chrome_137"), "{}", error);
+
+        assert!(resolve_profile("chrome_121", false).is_err());
+        assert!(resolve_profile("chrome_121", true).is_ok());
+
+        let error = resolve_profile("zzzzzzzzzz", true).unwrap_err().to_string();
+        assert!(error.contains("see getProfiles()"), "{}", error);
+    }
+}
@@ -0,0 +1,752 @@
+use anyhow::Result;
+use once_cell::sync::Lazy;
+use rand::Rng;
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::time::Instant;
+use url::{Host, Url};
+use wreq::header::HeaderValue;
+
+use crate::error::{classify, invalid, Error, ErrorKind, TimeoutPhase};
+
+// Global storage for proxy pools
+static PROXY_POOLS: Lazy<StdMutex<HashMap<u64, Arc<ProxyPool>>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+static NEXT_POOL_ID: Lazy<StdMutex<u64>> = Lazy::new(|| StdMutex::new(1));
+
+// Hosts a sticky pool remembers before making room for new ones
+const MAX_STICKY_HOSTS: usize = 1024;
+
+/// How a pool picks the proxy for the next request
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationStrategy {
+    /// Each available proxy in turn
+    #[default]
+    RoundRobin,
+    /// Any available proxy
+    Random,
+    /// The same proxy for every request to a host, as long as it stays available
+    StickyHost,
+    /// The proxy that failed least often (the least used one on a tie)
+    LeastFailures,
+}
+
+impl RotationStrategy {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "round-robin" => Some(Self::RoundRobin),
+            "random" => Some(Self::Random),
+            "sticky-host" => Some(Self::StickyHost),
+            "least-failures" => Some(Self::LeastFailures),
+            _ => None,
+        }
+    }
+}
+
+/// Rotation and ejection settings of a pool
+#[derive(Debug, Clone)]
+pub struct PoolOptions {
+    pub strategy: RotationStrategy,
+    /// Consecutive failures after which a proxy is ejected
+    pub max_failures: u32,
+    /// How long an ejected proxy sits out before it is tried again
+    pub cooldown_ms: u64,
+}
+
+impl Default for PoolOptions {
+    fn default() -> Self {
+        Self {
+            strategy: RotationStrategy::default(),
+            max_failures: 3,
+            cooldown_ms: 30_000,
+        }
+    }
+}
+
+/// What a request through a proxy says about the proxy's health
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyOutcome {
+    Success,
+    Failure,
+    /// The request failed for a reason that has nothing to do with the proxy
+    Unknown,
+}
+
+impl ProxyOutcome {
+    /// A response came back; 407 means the proxy turned the credentials down
+    pub fn of_status(status: u16) -> Self {
+        if status == 407 {
+            Self::Failure
+        } else {
+            Self::Success
+        }
+    }
+
+    /// Refused or timed out connections (they go to the proxy) and CONNECT or proxy
+    /// TLS errors count against the proxy; TLS and reset errors past it are the target's
+    pub fn of_error(error: &anyhow::Error) -> Self {
+        match classify(error) {
+            ErrorKind::Proxy | ErrorKind::ConnectionRefused => Self::Failure,
+            ErrorKind::Timeout
+                if Error::from_anyhow(error).phase == Some(TimeoutPhase::Connect) =>
+            {
+                Self::Failure
+            }
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Proxy picked for a request
+#[derive(Debug, Clone)]
+pub struct PickedProxy {
+    /// URL as given to the pool or settings, reported back in the response
+    pub url: String,
+    pub proxy: wreq::Proxy,
+}
+
+/// Proxy settings as given in the `proxy` option
+#[derive(Debug, Clone, Default)]
+pub struct ProxySettings {
+    /// Proxy for requests of any scheme without a more specific one
+    pub all: Option<String>,
+    /// Proxy for `http://` and `ws://` requests
+    pub http: Option<String>,
+    /// Proxy for `https://` and `wss://` requests
+    pub https: Option<String>,
+    /// Credentials sent to the proxy, instead of the ones in its URL
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// `Proxy-Authorization` value sent as-is (e.g. a bearer token)
+    pub authorization: Option<String>,
+    /// Hosts reached directly: domains, `*.domain` wildcards, IPs and CIDR ranges
+    pub bypass: Vec<String>,
+    /// Fill in missing proxies and extend the bypass list from `HTTP_PROXY`,
+    /// `HTTPS_PROXY`, `ALL_PROXY` and `NO_PROXY`
+    pub from_env: bool,
+}
+
+impl ProxySettings {
+    /// One proxy for every scheme
+    pub fn url(url: String) -> Self {
+        Self {
+            all: Some(url),
+            ..Self::default()
+        }
+    }
+}
+
+/// Proxies to route requests through, picked by scheme and host
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    all: Option<PickedProxy>,
+    http: Option<PickedProxy>,
+    https: Option<PickedProxy>,
+    bypass: Vec<BypassRule>,
+}
+
+impl ProxyConfig {
+    pub fn new(mut settings: ProxySettings) -> Result<Self> {
+        if settings.from_env {
+            settings.all = settings.all.or_else(|| env_var("ALL_PROXY"));
+            settings.http = settings.http.or_else(|| env_var("HTTP_PROXY"));
+            settings.https = settings.https.or_else(|| env_var("HTTPS_PROXY"));
+        }
+
+        let mut bypass = settings
+            .bypass
+            .iter()
+            .map(|entry| {
+                BypassRule::parse(entry)
+                    .ok_or_else(|| invalid(format!("Invalid proxy bypass entry: {}", entry)))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Malformed NO_PROXY entries are skipped, as other clients do
+        if settings.from_env {
+            if let Some(no_proxy) = env_var("NO_PROXY") {
+                bypass.extend(no_proxy.split(',').filter_map(BypassRule::parse));
+            }
+        }
+
+        let proxy = |url: &Option<String>| -> Result<Option<PickedProxy>> {
+            let Some(url) = url else {
+                return Ok(None);
+            };
+
+            let mut proxy = wreq::Proxy::all(url)
+                .map_err(|e| invalid(format!("Invalid proxy URL {}: {}", url, e)))?;
+
+            if let Some(username) = &settings.username {
+                proxy = proxy.basic_auth(username, settings.password.as_deref().unwrap_or(""));
+            }
+
+            if let Some(authorization) = &settings.authorization {
+                let value = HeaderValue::from_str(authorization)
+                    .map_err(|_| invalid("Invalid proxy authorization header value"))?;
+                proxy = proxy.custom_http_auth(value);
+            }
+
+            Ok(Some(PickedProxy {
+                url: url.clone(),
+                proxy,
+            }))
+        };
+
+        Ok(Self {
+            all: proxy(&settings.all)?,
+            http: proxy(&settings.http)?,
+            https: proxy(&settings.https)?,
+            bypass,
+        })
+    }
+
+    /// Proxy a request to `url` goes through, `None` to connect directly
+    pub fn route(&self, url: &Url) -> Option<&PickedProxy> {
+        let scheme_proxy = match url.scheme() {
+            "http" | "ws" => self.http.as_ref(),
+            "https" | "wss" => self.https.as_ref(),
+            _ => None,
+        };
+        let proxy = scheme_proxy.or(self.all.as_ref())?;
+
+        let host = url.host()?;
+        if self.bypass.iter().any(|rule| rule.matches(&host)) {
+            return None;
+        }
+
+        Some(proxy)
+    }
+}
+
+/// Host reached without the proxy
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BypassRule {
+    /// `*`: every host
+    Any,
+    /// `example.com` or `.example.com`: the domain and its subdomains
+    Domain(String),
+    /// `*.example.com`: subdomains only
+    Subdomains(String),
+    /// An IP address, or a CIDR range with its prefix length
+    Network(IpAddr, u8),
+}
+
+impl BypassRule {
+    fn parse(entry: &str) -> Option<Self> {
+        let entry = entry.trim().to_ascii_lowercase();
+
+        if entry.is_empty() {
+            return None;
+        }
+        if entry == "*" {
+            return Some(Self::Any);
+        }
+
+        if let Some((address, prefix)) = entry.split_once('/') {
+            let address: IpAddr = address.parse().ok()?;
+            let prefix: u8 = prefix.parse().ok()?;
+            let max_prefix = if address.is_ipv4() { 32 } else { 128 };
+            return (prefix <= max_prefix).then_some(Self::Network(address, prefix));
+        }
+
+        let address = entry.trim_start_matches('[').trim_end_matches(']');
+        if let Ok(address) = address.parse::<IpAddr>() {
+            let prefix = if address.is_ipv4() { 32 } else { 128 };
+            return Some(Self::Network(address, prefix));
+        }
+
+        // A port (as in `NO_PROXY=example.com:8080`) doesn't narrow the rule down
+        let domain = entry.split(':').next().unwrap_or_default();
+
+        if let Some(domain) = domain.strip_prefix("*.") {
+            return valid_domain(domain).then(|| Self::Subdomains(domain.to_string()));
+        }
+
+        let domain = domain.trim_start_matches('.');
+        valid_domain(domain).then(|| Self::Domain(domain.to_string()))
+    }
+
+    fn matches(&self, host: &Host<&str>) -> bool {
+        match (self, host) {
+            (Self::Any, _) => true,
+            (Self::Domain(domain), Host::Domain(host)) => {
+                host.eq_ignore_ascii_case(domain) || is_subdomain(host, domain)
+            }
+            (Self::Subdomains(domain), Host::Domain(host)) => is_subdomain(host, domain),
+            (Self::Network(network, prefix), Host::Ipv4(address)) => {
+                in_network(&IpAddr::V4(*address), network, *prefix)
+            }
+            (Self::Network(network, prefix), Host::Ipv6(address)) => {
+                in_network(&IpAddr::V6(*address), network, *prefix)
+            }
+            _ => false,
+        }
+    }
+}
+
+fn valid_domain(domain: &str) -> bool {
+    !domain.is_empty()
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+fn is_subdomain(host: &str, domain: &str) -> bool {
+    host.to_ascii_lowercase().ends_with(&format!(".{}", domain))
+}
+
+fn in_network(address: &IpAddr, network: &IpAddr, prefix: u8) -> bool {
+    match (address, network) {
+        (IpAddr::V4(address), IpAddr::V4(network)) => {
+            let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+            u32::from(*address) & mask == u32::from(*network) & mask
+        }
+        (IpAddr::V6(address), IpAddr::V6(network)) => {
+            let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+            u128::from(*address) & mask == u128::from(*network) & mask
+        }
+        _ => false,
+    }
+}
+
+// Proxy environment variable, upper or lower case, ignoring empty ones
+fn env_var(name: &str) -> Option<String> {
+    std::env::var(name)
+        .or_else(|_| std::env::var(name.to_ascii_lowercase()))
+        .ok()
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// Health of one proxy of a pool
+#[derive(Debug, Clone)]
+pub struct ProxyStats {
+    pub url: String,
+    /// Not ejected (or back from its cooldown)
+    pub available: bool,
+    /// Failures since the last success
+    pub consecutive_failures: u32,
+    pub failures: u64,
+    pub successes: u64,
+    /// Time left until an ejected proxy is tried again
+    pub cooldown_remaining_ms: Option<u64>,
+}
+
+#[derive(Debug)]
+struct PoolEntry {
+    url: String,
+    proxy: wreq::Proxy,
+    consecutive_failures: u32,
+    failures: u64,
+    successes: u64,
+    uses: u64,
+    ejected_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+struct PoolState {
+    entries: Vec<PoolEntry>,
+    /// Next position for round-robin
+    cursor: usize,
+    /// Proxy each host sticks to with `RotationStrategy::StickyHost`
+    sticky: HashMap<String, usize>,
+}
+
+/// Proxies requests rotate through, with failing ones ejected for a cooldown
+#[derive(Debug)]
+pub struct ProxyPool {
+    options: PoolOptions,
+    state: StdMutex<PoolState>,
+}
+
+impl ProxyPool {
+    pub fn new(urls: &[String], options: PoolOptions) -> Result<Self> {
+        if urls.is_empty() {
+            return Err(invalid("A proxy pool needs at least one proxy"));
+        }
+
+        if options.max_failures == 0 {
+            return Err(invalid("maxFailures must be at least 1"));
+        }
+
+        let entries = urls
+            .iter()
+            .enumerate()
+            .map(|(index, url)| {
+                // Health is tracked by URL, so each proxy may only be listed once
+                if urls[..index].contains(url) {
+                    return Err(invalid(format!("Proxy listed twice: {}", url)));
+                }
+
+                let proxy = wreq::Proxy::all(url)
+                    .map_err(|e| invalid(format!("Invalid proxy URL {}: {}", url, e)))?;
+
+                Ok(PoolEntry {
+                    url: url.clone(),
+                    proxy,
+                    consecutive_failures: 0,
+                    failures: 0,
+                    successes: 0,
+                    uses: 0,
+                    ejected_until: None,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            options,
+            state: StdMutex::new(PoolState {
+                entries,
+                cursor: 0,
+                sticky: HashMap::new(),
+            }),
+        })
+    }
+
+    /// Pick the proxy for a request to `host`
+    pub fn pick(&self, host: &str) -> Result<PickedProxy> {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        // Proxies whose cooldown is over get back in, one failure away from ejection
+        let max_failures = self.options.max_failures;
+        for entry in &mut state.entries {
+            if entry.ejected_until.is_some_and(|until| until <= now) {
+                entry.ejected_until = None;
+                entry.consecutive_failures = max_failures - 1;
+            }
+        }
+
+        let available: Vec<usize> = (0..state.entries.len())
+            .filter(|&index| state.entries[index].ejected_until.is_none())
+            .collect();
+
+        if available.is_empty() {
+            let next = state
+                .entries
+                .iter()
+                .filter_map(|entry| entry.ejected_until)
+                .min()
+                .map(|until| until.saturating_duration_since(now))
+                .unwrap_or_default();
+
+            return Err(Error::new(
+                ErrorKind::Proxy,
+                format!(
+                    "Every proxy of the pool is cooling down (next one back in {}ms)",
+                    next.as_millis()
+                ),
+            )
+            .into());
+        }
+
+        let index = match self.options.strategy {
+            RotationStrategy::RoundRobin => next_in_turn(&mut state, &available),
+            RotationStrategy::Random => available[rand::rng().random_range(0..available.len())],
+            RotationStrategy::StickyHost => match state.sticky.get(host).copied() {
+                Some(index) if available.contains(&index) => index,
+                _ => {
+                    let index = next_in_turn(&mut state, &available);
+                    if state.sticky.len() >= MAX_STICKY_HOSTS && !state.sticky.contains_key(host) {
+                        make_sticky_room(&mut state.sticky);
+                    }
+                    state.sticky.insert(host.to_string(), index);
+                    index
+                }
+            },
+            RotationStrategy::LeastFailures => *available
+                .iter()
+                .min_by_key(|&&index| {
+                    let entry = &state.entries[index];
+                    (entry.failures, entry.uses)
+                })
+                .expect("at least one proxy is available"),
+        };
+
+        let entry = &mut state.entries[index];
+        entry.uses += 1;
+
+        Ok(PickedProxy {
+            url: entry.url.clone(),
+            proxy: entry.proxy.clone(),
+        })
+    }
+
+    /// Record how a request through `url` went, ejecting the proxy once it
+    /// failed `max_failures` times in a row
+    pub fn record(&self, url: &str, outcome: ProxyOutcome) {
+        let mut state = self.state.lock().unwrap();
+        let Some(index) = state.entries.iter().position(|entry| entry.url == url) else {
+            return;
+        };
+        let entry = &mut state.entries[index];
+
+        match outcome {
+            ProxyOutcome::Success => {
+                entry.successes += 1;
+                entry.consecutive_failures = 0;
+            }
+            ProxyOutcome::Failure => {
+                entry.failures += 1;
+                entry.consecutive_failures += 1;
+
+                if entry.consecutive_failures >= self.options.max_failures
+                    && entry.ejected_until.is_none()
+                {
+                    entry.ejected_until =
+                        Some(Instant::now() + Duration::from_millis(self.options.cooldown_ms));
+
+                    // Its hosts pick another proxy next time anyway
+                    state.sticky.retain(|_, &mut stuck| stuck != index);
+                }
+            }
+            ProxyOutcome::Unknown => {}
+        }
+    }
+
+    /// Health of every proxy, in the order they were given
+    pub fn stats(&self) -> Vec<ProxyStats> {
+        let state = self.state.lock().unwrap();
+        let now = Instant::now();
+
+        state
+            .entries
+            .iter()
+            .map(|entry| {
+                let cooldown = entry
+                    .ejected_until
+                    .map(|until| until.saturating_duration_since(now))
+                    .filter(|remaining| !remaining.is_zero());
+
+                ProxyStats {
+                    url: entry.url.clone(),
+                    available: cooldown.is_none(),
+                    consecutive_failures: entry.consecutive_failures,
+                    failures: entry.failures,
+                    successes: entry.successes,
+                    cooldown_remaining_ms: cooldown.map(|remaining| remaining.as_millis() as u64),
+                }
+            })
+            .collect()
+    }
+}
+
+// Forget a sticky host to stay within `MAX_STICKY_HOSTS` (an arbitrary one:
+// its next request simply picks a proxy again)
+fn make_sticky_room(sticky: &mut HashMap<String, usize>) {
+    if let Some(host) = sticky.keys().next().cloned() {
+        sticky.remove(&host);
+    }
+}
+
+// Next available proxy at or after the round-robin cursor
+fn next_in_turn(state: &mut PoolState, available: &[usize]) -> usize {
+    let index = available
+        .iter()
+        .copied()
+        .find(|&index| index >= state.cursor)
+        .unwrap_or(available[0]);
+
+    state.cursor = index + 1;
+    index
+}
+
+/// Store a proxy pool and return its ID
+pub fn store_pool(pool: Arc<ProxyPool>) -> u64 {
+    let mut id_lock = NEXT_POOL_ID.lock().unwrap();
+    let id = *id_lock;
+    *id_lock += 1;
+    drop(id_lock);
+
+    let mut pools = PROXY_POOLS.lock().unwrap();
+    pools.insert(id, pool);
+    id
+}
+
+/// Get a proxy pool by ID
+pub fn get_pool(id: u64) -> Option<Arc<ProxyPool>> {
+    let pools = PROXY_POOLS.lock().unwrap();
+    pools.get(&id).cloned()
+}
+
+/// Remove a proxy pool (clients using it keep their reference)
+pub fn remove_pool(id: u64) {
+    let mut pools = PROXY_POOLS.lock().unwrap();
+    pools.remove(&id);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIRST: &str = "http://127.0.0.1:8080";
+    const SECOND: &str = "http://127.0.0.2:8080";
+
+    #[test]
+    fn pool_rotates_and_ejects_failing_proxies() {
+        let urls = [FIRST.to_string(), SECOND.to_string()];
+        let options = PoolOptions {
+            max_failures: 2,
+            cooldown_ms: 60_000,
+            ..PoolOptions::default()
+        };
+        let pool = ProxyPool::new(&urls, options).unwrap();
+
+        let picks: Vec<String> = (0..3)
+            .map(|_| pool.pick("example.com").unwrap().url)
+            .collect();
+        assert_eq!(picks, [FIRST, SECOND, FIRST]);
+
+        // Only failures in a row count, unrelated errors don't count at all
+        pool.record(FIRST, ProxyOutcome::Failure);
+        pool.record(FIRST, ProxyOutcome::Success);
+        pool.record(FIRST, ProxyOutcome::Failure);
+        pool.record(FIRST, ProxyOutcome::Unknown);
+        assert!(pool.stats()[0].available);
+
+        pool.record(FIRST, ProxyOutcome::Failure);
+        assert!(!pool.stats()[0].available);
+        assert_eq!(pool.pick("example.com").unwrap().url, SECOND);
+        assert_eq!(pool.pick("example.com").unwrap().url, SECOND);
+
+        pool.record(SECOND, ProxyOutcome::Failure);
+        pool.record(SECOND, ProxyOutcome::Failure);
+        let error = pool.pick("example.com").unwrap_err();
+        assert_eq!(classify(&error), ErrorKind::Proxy);
+    }
+
+    #[test]
+    fn cooled_down_proxies_return_one_failure_from_ejection() {
+        let options = PoolOptions {
+            max_failures: 3,
+            cooldown_ms: 0,
+            ..PoolOptions::default()
+        };
+        let pool = ProxyPool::new(&[FIRST.to_string()], options).unwrap();
+        for _ in 0..3 {
+            pool.record(FIRST, ProxyOutcome::Failure);
+        }
+
+        assert_eq!(pool.pick("example.com").unwrap().url, FIRST);
+        assert_eq!(pool.stats()[0].consecutive_failures, 2);
+    }
+
+    #[test]
+    fn only_proxy_side_errors_count_against_the_proxy() {
+        let outcome = |kind| ProxyOutcome::of_error(&Error::new(kind, "failed").into());
+
+        assert_eq!(outcome(ErrorKind::Proxy), ProxyOutcome::Failure);
+        assert_eq!(outcome(ErrorKind::ConnectionRefused), ProxyOutcome::Failure);
+        assert_eq!(
+            ProxyOutcome::of_error(&Error::timeout(TimeoutPhase::Connect, "timed out").into()),
+            ProxyOutcome::Failure
+        );
+        assert_eq!(outcome(ErrorKind::Tls), ProxyOutcome::Unknown);
+        assert_eq!(outcome(ErrorKind::ConnectionReset), ProxyOutcome::Unknown);
+        assert_eq!(ProxyOutcome::of_status(407), ProxyOutcome::Failure);
+        assert_eq!(ProxyOutcome::of_status(503), ProxyOutcome::Success);
+    }
+
+    #[test]
+    fn sticky_hosts_keep_their_proxy() {
+        let urls = [FIRST.to_string(), SECOND.to_string()];
+        let options = PoolOptions {
+            strategy: RotationStrategy::StickyHost,
+            ..PoolOptions::default()
+        };
+        let pool = ProxyPool::new(&urls, options).unwrap();
+
+        assert_eq!(pool.pick("a.example").unwrap().url, FIRST);
+        assert_eq!(pool.pick("b.example").unwrap().url, SECOND);
+        assert_eq!(pool.pick("a.example").unwrap().url, FIRST);
+    }
+
+    #[test]
+    fn pool_rejects_invalid_setups() {
+        assert!(ProxyPool::new(&[], PoolOptions::default()).is_err());
+        assert!(ProxyPool::new(
+            &[FIRST.to_string(), FIRST.to_string()],
+            PoolOptions::default()
+        )
+        .is_err());
+
+        let options = PoolOptions {
+            max_failures: 0,
+            ..PoolOptions::default()
+        };
+        assert!(ProxyPool::new(&[FIRST.to_string()], options).is_err());
+    }
+
+    #[test]
+    fn sticky_hosts_are_bounded_and_dropped_with_their_proxy() {
+        let urls = [FIRST.to_string(), SECOND.to_string()];
+        let options = PoolOptions {
+            strategy: RotationStrategy::StickyHost,
+            max_failures: 1,
+            ..PoolOptions::default()
+        };
+        let pool = ProxyPool::new(&urls, options).unwrap();
+        for index in 0..MAX_STICKY_HOSTS + 10 {
+            pool.pick(&format!("host{}.example", index)).unwrap();
+        }
+        assert_eq!(pool.state.lock().unwrap().sticky.len(), MAX_STICKY_HOSTS);
+
+        pool.record(FIRST, ProxyOutcome::Failure);
+        let state = pool.state.lock().unwrap();
+        assert!(state.sticky.values().all(|&index| index == 1));
+    }
+
+    #[test]
+    fn bypass_rules_match_domains_and_networks() {
+        let cases = [
+            ("example.com", "https://api.example.com/", true),
+            (".example.com", "https://example.com/", true),
+            ("example.com", "https://badexample.com/", false),
+            ("example.com", "https://example.com.evil.net/", false),
+            ("*.example.com", "https://example.com/", false),
+            ("example.com:8080", "http://example.com:9090/", true),
+            ("10.0.0.0/8", "http://10.1.2.3/", true),
+            ("10.0.0.0/8", "http://[::ffff:10.0.0.1]/", false),
+            ("2001:db8::/32", "http://[2001:db9::1]/", false),
+            ("[::1]", "http://[::1]/", true),
+        ];
+        for (entry, url, expected) in cases {
+            let url = Url::parse(url).unwrap();
+            let matched = BypassRule::parse(entry)
+                .unwrap()
+                .matches(&url.host().unwrap());
+            assert_eq!(matched, expected, "{} against {}", entry, url);
+        }
+
+        for entry in ["", "10.0.0.0/33", "::1/129", "exa mple.com", "*."] {
+            assert_eq!(BypassRule::parse(entry), None, "{}", entry);
+        }
+    }
+
+    #[test]
+    fn config_routes_by_scheme_and_bypass() {
+        let config = ProxyConfig::new(ProxySettings {
+            all: Some("http://all.proxy:8080".to_string()),
+            https: Some("http://secure.proxy:8080".to_string()),
+            bypass: vec!["internal.example".to_string(), "10.0.0.0/8".to_string()],
+            ..ProxySettings::default()
+        })
+        .unwrap();
+
+        let route = |url: &str| {
+            config
+                .route(&Url::parse(url).unwrap())
+                .map(|proxy| proxy.url.as_str())
+        };
+        assert_eq!(
+            route("wss://example.com/"),
+            Some("http://secure.proxy:8080")
+        );
+        assert_eq!(route("http://example.com/"), Some("http://all.proxy:8080"));
+        assert_eq!(route("https://api.internal.example/"), None);
+        assert_eq!(route("http://10.20.30.40/"), None);
+    }
+}
@@ -0,0 +1,246 @@
+use anyhow::{anyhow, Context, Result};
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::{Arc, Mutex as StdMutex};
+use tokio::io::AsyncReadExt;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::abort::{abortable, AbortToken};
+use crate::timeout::{run_until, BodyTimeouts};
+
+// Global storage for response bodies being streamed to JS
+static BODY_STREAMS: Lazy<StdMutex<HashMap<u64, Arc<BodyStream>>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+static NEXT_STREAM_ID: Lazy<StdMutex<u64>> = Lazy::new(|| StdMutex::new(1));
+
+// Global storage for request bodies being written from JS
+static BODY_SINKS: Lazy<StdMutex<HashMap<u64, Arc<BodySink>>>> =
+    Lazy::new(|| StdMutex::new(HashMap::new()));
+
+static NEXT_SINK_ID: Lazy<StdMutex<u64>> = Lazy::new(|| StdMutex::new(1));
+
+/// Chunks buffered between JS writes and the socket before writes start waiting
+const SINK_CAPACITY: usize = 8;
+
+/// Read size used when uploading a file from disk
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+
+type Chunk = io::Result<Vec<u8>>;
+
+/// Response body read chunk by chunk as JS asks for it.
+///
+/// Nothing is read from the socket until `next_chunk` is called, so a slow
+/// consumer applies backpressure all the way down to the connection.
+pub struct BodyStream {
+    inner: Mutex<BoxStream<'static, Result<Vec<u8>>>>,
+    /// Abort token of the request, still watched while the body is read
+    abort: Option<Arc<AbortToken>>,
+    timeouts: BodyTimeouts,
+}
+
+impl BodyStream {
+    pub fn new(
+        response: wreq::Response,
+        abort: Option<Arc<AbortToken>>,
+        timeouts: BodyTimeouts,
+    ) -> Self {
+        let inner = response
+            .bytes_stream()
+            .map(|chunk| {
+                chunk
+                    .map(|bytes| bytes.to_vec())
+                    .context("Failed to read response body")
+            })
+            .boxed();
+
+        Self {
+            inner: Mutex::new(inner),
+            abort,
+            timeouts,
+        }
+    }
+
+    /// Read the next chunk, `None` once the body is complete
+    pub async fn next_chunk(&self) -> Result<Option<Vec<u8>>> {
+        let mut inner = self.inner.lock().await;
+        let next = run_until(self.timeouts.next_chunk(), async {
+            inner.next().await.transpose()
+        });
+        abortable(self.abort.as_deref(), next).await
+    }
+}
+
+/// Request body written chunk by chunk from JS.
+///
+/// The channel is bounded, so a write only resolves once there is room for
+/// it: JS can't get further ahead of the socket than `SINK_CAPACITY` chunks.
+#[derive(Debug)]
+pub struct BodySink {
+    sender: StdMutex<Option<mpsc::Sender<Chunk>>>,
+    receiver: StdMutex<Option<mpsc::Receiver<Chunk>>>,
+}
+
+impl Default for BodySink {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BodySink {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::channel(SINK_CAPACITY);
+
+        Self {
+            sender: StdMutex::new(Some(sender)),
+            receiver: StdMutex::new(Some(receiver)),
+        }
+    }
+
+    /// Queue a chunk, waiting while the channel is full
+    pub async fn write(&self, chunk: Vec<u8>) -> Result<()> {
+        let sender = self.sender.lock().unwrap().clone();
+        let sender = sender.ok_or_else(|| anyhow!("Request body stream is already finished"))?;
+
+        sender
+            .send(Ok(chunk))
+            .await
+            .map_err(|_| anyhow!("Request body stream is closed (request ended)"))
+    }
+
+    /// Mark the end of the body
+    pub fn finish(&self) {
+        self.sender.lock().unwrap().take();
+    }
+
+    /// Fail the request that is sending this body
+    pub async fn abort(&self, message: String) {
+        let sender = self.sender.lock().unwrap().take();
+
+        if let Some(sender) = sender {
+            let _ = sender.send(Err(io::Error::other(message))).await;
+        }
+    }
+
+    /// Hand the receiving end to a request (only once)
+    pub fn take_receiver(&self) -> Result<StreamBody> {
+        let receiver = self
+            .receiver
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| anyhow!("Request body stream is already used by another request"))?;
+
+        Ok(StreamBody {
+            receiver: StdMutex::new(Some(receiver)),
+        })
+    }
+}
+
+/// Receiving end of a `BodySink`, owned by the request sending it.
+///
+/// Dropping it (request finished or failed) makes pending JS writes fail
+/// instead of waiting forever.
+#[derive(Debug)]
+pub struct StreamBody {
+    receiver: StdMutex<Option<mpsc::Receiver<Chunk>>>,
+}
+
+impl StreamBody {
+    /// Turn the chunks into a streamed `wreq::Body` (only once)
+    pub fn take_body(&self) -> Result<wreq::Body> {
+        let receiver = self
+            .receiver
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or_else(|| anyhow!("Request body stream was already sent"))?;
+
+        let chunks = futures_util::stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        });
+
+        Ok(wreq::Body::wrap_stream(chunks))
+    }
+}
+
+/// Stream a file from disk as a request body, returning it with its length
+pub async fn file_body(path: &Path) -> Result<(wreq::Body, u64)> {
+    let file = tokio::fs::File::open(path)
+        .await
+        .with_context(|| format!("Failed to open request body file {}", path.display()))?;
+    let length = file
+        .metadata()
+        .await
+        .with_context(|| format!("Failed to read metadata of {}", path.display()))?
+        .len();
+
+    let chunks = futures_util::stream::unfold(Some(file), |file| async move {
+        let mut file = file?;
+        let mut buffer = vec![0u8; FILE_CHUNK_SIZE];
+
+        match file.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(read) => {
+                buffer.truncate(read);
+                Some((Ok(buffer), Some(file)))
+            }
+            // Yield the error once, then end the stream
+            Err(e) => Some((Err(e), None)),
+        }
+    });
+
+    Ok((wreq::Body::wrap_stream(chunks), length))
+}
+
+/// Store a body sink and return its ID
+pub fn store_sink(sink: BodySink) -> u64 {
+    let mut id_lock = NEXT_SINK_ID.lock().unwrap();
+    let id = *id_lock;
+    *id_lock += 1;
+    drop(id_lock);
+
+    let mut sinks = BODY_SINKS.lock().unwrap();
+    sinks.insert(id, Arc::new(sink));
+    id
+}
+
+/// Get a body sink by ID
+pub fn get_sink(id: u64) -> Option<Arc<BodySink>> {
+    let sinks = BODY_SINKS.lock().unwrap();
+    sinks.get(&id).cloned()
+}
+
+/// Remove a body sink (the request keeps its own reference)
+pub fn remove_sink(id: u64) {
+    let mut sinks = BODY_SINKS.lock().unwrap();
+    sinks.remove(&id);
+}
+
+/// Store a body stream and return its ID
+pub fn store_stream(stream: BodyStream) -> u64 {
+    let mut id_lock = NEXT_STREAM_ID.lock().unwrap();
+    let id = *id_lock;
+    *id_lock += 1;
+    drop(id_lock);
+
+    let mut streams = BODY_STREAMS.lock().unwrap();
+    streams.insert(id, Arc::new(stream));
+    id
+}
+
+/// Get a body stream by ID
+pub fn get_stream(id: u64) -> Option<Arc<BodyStream>> {
+    let streams = BODY_STREAMS.lock().unwrap();
+    streams.get(&id).cloned()
+}
+
+/// Remove a body stream; dropping it closes the underlying connection
+pub fn remove_stream(id: u64) {
+    let mut streams = BODY_STREAMS.lock().unwrap();
+    streams.remove(&id);
+}
@@ -0,0 +1,87 @@
+use anyhow::Result;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::error::{Error, TimeoutPhase};
+
+/// Point in time a request phase has to finish by
+#[derive(Debug, Clone, Copy)]
+pub struct Deadline {
+    at: Instant,
+    phase: TimeoutPhase,
+    timeout_ms: u64,
+}
+
+impl Deadline {
+    /// Deadline `timeout_ms` from now, `None` without a timeout
+    pub fn after(timeout_ms: Option<u64>, phase: TimeoutPhase) -> Option<Self> {
+        timeout_ms.map(|timeout_ms| Self {
+            at: Instant::now() + Duration::from_millis(timeout_ms),
+            phase,
+            timeout_ms,
+        })
+    }
+
+    /// Whichever of two deadlines comes first
+    pub fn earliest(a: Option<Self>, b: Option<Self>) -> Option<Self> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(if b.at < a.at { b } else { a }),
+            (a, b) => a.or(b),
+        }
+    }
+
+    /// Time left until the deadline
+    pub fn remaining(&self) -> Duration {
+        self.at.saturating_duration_since(Instant::now())
+    }
+
+    fn error(&self) -> anyhow::Error {
+        let option = match self.phase {
+            TimeoutPhase::Connect => "connectTimeout",
+            TimeoutPhase::ResponseHeader => "responseHeaderTimeout",
+            TimeoutPhase::Read => "readTimeout",
+            TimeoutPhase::Total => "totalTimeout",
+        };
+
+        Error::timeout(
+            self.phase,
+            format!("Timed out after {}ms ({})", self.timeout_ms, option),
+        )
+        .into()
+    }
+}
+
+/// Run `future` until `deadline`, failing with a timeout error naming its phase
+pub async fn run_until<T>(
+    deadline: Option<Deadline>,
+    future: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    let Some(deadline) = deadline else {
+        return future.await;
+    };
+
+    match tokio::time::timeout_at(deadline.at, future).await {
+        Ok(result) => result,
+        Err(_) => Err(deadline.error()),
+    }
+}
+
+/// Timeouts still running while a response body is read
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BodyTimeouts {
+    /// Maximum idle time between two chunks
+    pub read_ms: Option<u64>,
+    /// Deadline of the whole request
+    pub total: Option<Deadline>,
+}
+
+impl BodyTimeouts {
+    /// Deadline for the next chunk to arrive
+    pub fn next_chunk(&self) -> Option<Deadline> {
+        Deadline::earliest(
+            Deadline::after(self.read_ms, TimeoutPhase::Read),
+            self.total,
+        )
+    }
+}
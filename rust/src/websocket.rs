@@ -6,8 +6,15 @@ use std::sync::{Arc, Mutex as StdMutex};
 use tokio::sync::Mutex;
 use wreq::ws::message::Message;
 use wreq::ws::WebSocket;
-use wreq_util::Emulation;
 use once_cell::sync::Lazy;
+use url::Url;
+
+use crate::cookies::{parse_set_cookies, CookieJar};
+use crate::headers::{orig_header_map, with_cookies, HeaderList, HeaderOrder, ProfileHeaders};
+use crate::network::NetworkOptions;
+use crate::profile::Profile;
+use crate::proxy::{ProxyConfig, ProxyOutcome, ProxyPool};
+use crate::tls::TlsOptions;
 
 // Global storage for WebSocket connections
 static WS_CONNECTIONS: Lazy<StdMutex<HashMap<u64, Arc<WsConnection>>>> =
@@ -26,22 +33,38 @@ pub static WS_RUNTIME: Lazy<tokio::runtime::Runtime> = Lazy::new(|| {
 #[derive(Debug, Clone)]
 pub struct WebSocketOptions {
     pub url: String,
-    pub emulation: Emulation,
-    pub headers: HashMap<String, String>,
-    pub proxy: Option<String>,
+    pub emulation: Profile,
+    pub headers: HeaderList,
+    /// Where custom headers go relative to the profile's defaults
+    pub header_order: HeaderOrder,
+    /// Changes to the profile's default headers
+    pub profile_headers: ProfileHeaders,
+    pub proxy: Option<ProxyConfig>,
+    /// Pool the connection picks its proxy from, instead of `proxy`
+    pub proxy_pool: Option<Arc<ProxyPool>>,
+    /// Local address or interface to connect from, and the preferred address family
+    pub network: NetworkOptions,
+    /// Extra root certificates, client certificate and verification of the handshake
+    pub tls: TlsOptions,
+    /// Jar whose cookies are sent with the handshake and that stores the ones it sets
+    pub cookie_jar: Option<Arc<CookieJar>>,
 }
 
 /// WebSocket connection wrapper
 pub struct WsConnection {
     sender: Arc<Mutex<futures_util::stream::SplitSink<WebSocket, Message>>>,
+    /// Proxy the connection went through (the one picked from a pool)
+    pub proxy: Option<String>,
 }
 
 impl WsConnection {
     pub fn new(
         sender: futures_util::stream::SplitSink<WebSocket, Message>,
+        proxy: Option<String>,
     ) -> Self {
         Self {
             sender: Arc::new(Mutex::new(sender)),
+            proxy,
         }
     }
 
@@ -105,15 +128,42 @@ pub fn remove_connection(id: u64) {
 
 /// Create WebSocket connection
 pub async fn connect_websocket(options: WebSocketOptions) -> Result<(WsConnection, futures_util::stream::SplitStream<WebSocket>)> {
-    // Build client with emulation and proxy
-    let mut client_builder = wreq::Client::builder()
-        .emulation(options.emulation);
+    // The client only serves this connection, so profile header changes go into its emulation
+    let mut emulation = options.emulation.emulation();
+    options.profile_headers.apply_to(emulation.headers_mut());
 
-    // Apply proxy if present
-    if let Some(proxy_url) = &options.proxy {
-        let proxy = wreq::Proxy::all(proxy_url).context("Failed to create proxy")?;
-        client_builder = client_builder.proxy(proxy);
+    let url = Url::parse(&options.url).with_context(|| format!("Invalid URL: {}", options.url))?;
+
+    // Build client with emulation and proxy (never the system one: env vars only
+    // apply through `ProxyConfig` with `fromEnv`)
+    let mut client_builder = wreq::Client::builder()
+        .emulation(emulation)
+        .no_proxy();
+
+    // Apply proxy if present: the pool's next one, or the one for the URL's scheme and host
+    let picked = match &options.proxy_pool {
+        Some(pool) => Some(pool.pick(url.host_str().unwrap_or_default())?),
+        None => None,
+    };
+    let proxy = picked
+        .as_ref()
+        .or_else(|| options.proxy.as_ref().and_then(|config| config.route(&url)));
+    if let Some(proxy) = proxy {
+        client_builder = client_builder.proxy(proxy.proxy.clone());
     }
+    let proxy_url = proxy.map(|proxy| proxy.url.clone());
+
+    // Bind the connection to a local address or interface, set up host name resolution
+    client_builder = options.network.configure(
+        client_builder,
+        &options.emulation,
+        &options.tls,
+        options.proxy.as_ref(),
+        options.proxy_pool.as_deref(),
+    )?;
+
+    // Trust extra roots, present a client certificate, or skip verification
+    client_builder = options.tls.configure(client_builder)?;
 
     // Build the client
     let client = client_builder
@@ -121,18 +171,39 @@ pub async fn connect_websocket(options: WebSocketOptions) -> Result<(WsConnectio
         .context("Failed to build HTTP client")?;
 
     // Create WebSocket request
-    let mut request = client.websocket(&options.url);
+    let mut request = client.websocket(url.as_str());
 
-    // Apply custom headers
-    for (key, value) in &options.headers {
+    // Apply custom headers in order (a Cookie header is merged with the jar's cookies)
+    let jar_cookies = options.cookie_jar.as_ref().and_then(|jar| jar.header_for(&url));
+    let headers = with_cookies(&options.headers, jar_cookies);
+    for (key, value) in &headers {
         request = request.header(key, value);
     }
 
+    if options.header_order == HeaderOrder::Request {
+        request = request.orig_headers(orig_header_map(&headers));
+    }
+
     // Send upgrade request
     let ws_response = request
         .send()
         .await
-        .context("Failed to send WebSocket upgrade request")?;
+        .context("Failed to send WebSocket upgrade request");
+
+    // The handshake tells the pool whether its proxy works
+    if let (Some(pool), Some(picked)) = (&options.proxy_pool, &picked) {
+        let outcome = match &ws_response {
+            Ok(response) => ProxyOutcome::of_status(response.status().as_u16()),
+            Err(error) => ProxyOutcome::of_error(error),
+        };
+        pool.record(&picked.url, outcome);
+    }
+    let ws_response = ws_response?;
+
+    // Keep cookies set by the handshake response
+    if let Some(jar) = &options.cookie_jar {
+        jar.store_response(&url, &parse_set_cookies(ws_response.headers()));
+    }
 
     // Upgrade to WebSocket
     let websocket = ws_response.into_websocket().await?;
@@ -140,7 +211,7 @@ pub async fn connect_websocket(options: WebSocketOptions) -> Result<(WsConnectio
     // Split into sender and receiver
     let (sender, receiver) = websocket.split();
 
-    let connection = WsConnection::new(sender);
+    let connection = WsConnection::new(sender, proxy_url);
 
     Ok((connection, receiver))
 }
@@ -1,6 +1,16 @@
 import { test, describe, before } from 'node:test';
 import assert from 'node:assert';
-import { request, getProfiles } from '../node-wreq';
+import {
+  request,
+  requestStream,
+  getProfiles,
+  defineProfile,
+  describeProfile,
+  seedProfileSelection,
+  createClient,
+  CookieJar,
+  ProxyPool,
+} from '../node-wreq';
 
 describe('HTTP', () => {
   before(() => {
@@ -22,6 +32,159 @@ describe('HTTP', () => {
     console.log('Available profiles:', profiles.join(', '));
   });
 
+  test('should describe browser profiles', () => {
+    const safari = describeProfile('safari_ios_18_1_1');
+    assert.strictEqual(safari.family, 'safari');
+    assert.strictEqual(safari.version, '18.1.1');
+    assert.strictEqual(safari.platform, 'ios');
+    assert.strictEqual(safari.custom, false);
+
+    assert.strictEqual(describeProfile('firefox_private_135').private, true);
+    assert.strictEqual(describeProfile('okhttp_4_12').platform, 'android');
+
+    const chrome = describeProfile('chrome_137');
+    assert.strictEqual(chrome.platform, 'desktop');
+    assert.strictEqual(chrome.http2, true);
+    assert.match(chrome.userAgent ?? '', /Chrome\/137/, 'Should report the default User-Agent');
+    assert.match(chrome.ja3, /^771,[\d-]+,[\d-]+,[\d-]+,[\d-]*$/);
+    assert.match(chrome.ja3Hash, /^[0-9a-f]{32}$/);
+    assert.match(chrome.ja4, /^t13d\d{4}h2_[0-9a-f]{12}_[0-9a-f]{12}$/);
+    assert.strictEqual(chrome.ja4, describeProfile('chrome_137').ja4, 'Should keep JA4 stable');
+    assert.match(
+      chrome.akamai ?? '',
+      /^1:65536;.*\|m,a,s,p$/,
+      'Should report the HTTP/2 fingerprint'
+    );
+
+    assert.throws(() => describeProfile('chrome137'), { code: 'EINVALIDREQUEST' });
+  });
+
+  test('should pick profiles with a seedable selector', async () => {
+    const selector = { family: 'chrome', minVersion: 130 };
+    const pick = async () => {
+      const response = await request({
+        url: 'https://httpbin.org/get',
+        browser: selector,
+        timeout: 10000,
+      });
+      return response.profile;
+    };
+
+    seedProfileSelection(42);
+    const first = [await pick(), await pick()];
+    seedProfileSelection(42);
+    const second = [await pick(), await pick()];
+    seedProfileSelection();
+
+    assert.deepStrictEqual(second, first, 'Should repeat the picks for the same seed');
+    for (const profile of first) {
+      const info = describeProfile(profile);
+      assert.strictEqual(info.family, 'chrome', 'Should keep to the family');
+      assert.ok(Number(info.version) >= 130, 'Should respect minVersion');
+    }
+
+    const weighted = await request({
+      url: 'https://httpbin.org/get',
+      browser: { weights: { safari_18: 1, firefox: 0 } },
+      timeout: 10000,
+    });
+    assert.strictEqual(weighted.profile, 'safari_18', 'Should follow the weights');
+
+    await assert.rejects(
+      request({ url: 'https://httpbin.org/get', browser: { weights: { chrome: 'many' as any } } }),
+      { name: 'RequestError', code: 'EINVALIDREQUEST', message: /Weight of chrome/ },
+      'Should reject a weight that is not a number'
+    );
+  });
+
+  test('should pair a profile with another operating system', async () => {
+    const headersFor = async (os: 'windows' | 'macos') => {
+      const response = await request({
+        url: 'https://httpbin.org/headers',
+        browser: 'chrome_137',
+        os,
+        timeout: 10000,
+      });
+      return response.json().headers;
+    };
+
+    const windows = await headersFor('windows');
+    assert.match(windows['User-Agent'], /Windows NT/, 'Should claim Windows');
+    assert.strictEqual(windows['Sec-Ch-Ua-Platform'], '"Windows"');
+
+    const mac = await headersFor('macos');
+    assert.match(mac['User-Agent'], /Macintosh/, 'Should claim macOS');
+    assert.strictEqual(mac['Sec-Ch-Ua-Platform'], '"macOS"');
+  });
+
+  test('should reject unknown browser profiles unless lenient', async () => {
+    await assert.rejects(
+      async () => {
+        await request({ url: 'https://httpbin.org/get', browser: 'chrome137' });
+      },
+      (error: Error & { code?: string }) => {
+        assert.strictEqual(error.name, 'RequestError');
+        assert.strictEqual(error.code, 'EINVALIDREQUEST');
+        assert.match(error.message, /did you mean chrome_137/, 'Should suggest near matches');
+        return true;
+      }
+    );
+
+    const response = await request({
+      url: 'https://httpbin.org/get',
+      browser: 'chrome_999',
+      lenientBrowser: true,
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, 'Should fall back to the nearest known version');
+  });
+
+  test('should request with a custom profile', async () => {
+    defineProfile('test_custom_profile', {
+      tls: { alpn: ['h2', 'http/1.1'], grease: true, minVersion: '1.2', maxVersion: '1.3' },
+      http2: {
+        initialWindowSize: 6291456,
+        pseudoHeaderOrder: [':method', ':authority', ':scheme', ':path'],
+      },
+      headers: [
+        ['User-Agent', 'CustomProfile/1.0'],
+        ['Accept', 'application/json'],
+      ],
+    });
+
+    assert.ok(getProfiles().includes('test_custom_profile'), 'Should list the custom profile');
+
+    const response = await request({
+      url: 'https://httpbin.org/headers',
+      browser: 'test_custom_profile',
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, 'Should return status 200');
+    assert.strictEqual(
+      response.json().headers['User-Agent'],
+      'CustomProfile/1.0',
+      'Should send the profile headers'
+    );
+
+    assert.throws(
+      () => defineProfile('test_invalid_profile', { tls: { alpn: ['spdy/3' as 'h2'] } }),
+      { name: 'RequestError', code: 'EINVALIDREQUEST' },
+      'Should reject an invalid spec'
+    );
+    assert.throws(
+      () => defineProfile('chrome_137', {}),
+      { code: 'EINVALIDREQUEST' },
+      'Should keep built-in names'
+    );
+    assert.throws(
+      () => defineProfile('random', {}),
+      { code: 'EINVALIDREQUEST' },
+      'Should keep random selection'
+    );
+  });
+
   test('should make a simple GET request', async () => {
     const response = await request({
       url: 'https://httpbin.org/get',
@@ -33,7 +196,7 @@ describe('HTTP', () => {
     assert.ok(Object.keys(response.headers).length > 0, 'Should have response headers');
     assert.ok(response.body.length > 0, 'Should have response body');
 
-    const body = JSON.parse(response.body);
+    const body = response.json();
 
     assert.ok(body.headers['User-Agent'], 'Should have User-Agent header');
 
@@ -54,7 +217,7 @@ describe('HTTP', () => {
 
       assert.ok(response.status === 200, `${browser} should return status 200`);
 
-      const data = JSON.parse(response.body);
+      const data = response.json();
 
       assert.ok(data['user-agent'], `${browser} should have user-agent`);
 
@@ -73,8 +236,566 @@ describe('HTTP', () => {
       },
       {
         name: 'RequestError',
+        code: 'ETIMEDOUT',
+        phase: 'total',
       },
       'Should throw an error on timeout'
     );
   });
+
+  test('should report which timeout phase expired', async () => {
+    await assert.rejects(
+      async () => {
+        await request({
+          url: 'https://httpbin.org/delay/10',
+          browser: 'chrome_137',
+          responseHeaderTimeout: 1000,
+        });
+      },
+      {
+        code: 'ETIMEDOUT',
+        phase: 'responseHeader',
+      },
+      'Should time out waiting for headers'
+    );
+
+    // Sends a chunk every 2 seconds
+    await assert.rejects(
+      async () => {
+        await request({
+          url: 'https://httpbin.org/drip?duration=10&numbytes=5&delay=0',
+          browser: 'chrome_137',
+          readTimeout: 1000,
+        });
+      },
+      {
+        code: 'ETIMEDOUT',
+        phase: 'read',
+      },
+      'Should time out between body chunks'
+    );
+  });
+
+  test('should cancel requests through an AbortSignal', async () => {
+    const controller = new AbortController();
+    const started = Date.now();
+
+    setTimeout(() => controller.abort(), 200);
+
+    await assert.rejects(
+      async () => {
+        await request({
+          url: 'https://httpbin.org/delay/10',
+          browser: 'chrome_137',
+          signal: controller.signal,
+        });
+      },
+      {
+        name: 'AbortError',
+        code: 'ABORT_ERR',
+      },
+      'Should reject with an AbortError'
+    );
+
+    assert.ok(Date.now() - started < 5000, 'Should not wait for the response');
+
+    await assert.rejects(
+      async () => {
+        await request({ url: 'https://httpbin.org/get', signal: AbortSignal.abort() });
+      },
+      {
+        name: 'AbortError',
+      },
+      'Should reject immediately when the signal is already aborted'
+    );
+  });
+
+  test('should report structured error details', async () => {
+    await assert.rejects(
+      async () => {
+        await request({
+          url: 'https://does-not-exist.invalid/',
+          browser: 'chrome_137',
+          timeout: 10000,
+        });
+      },
+      {
+        name: 'RequestError',
+        code: 'ENOTFOUND',
+        method: 'GET',
+        url: 'https://does-not-exist.invalid/',
+      },
+      'Should reject unresolvable hosts with ENOTFOUND'
+    );
+
+    await assert.rejects(
+      request({ url: 'https://tls-proxy.invalid/ssl', timeout: 10000 }),
+      { name: 'RequestError', code: 'ENOTFOUND' },
+      'Should not classify by words in the URL'
+    );
+  });
+
+  test('should support OPTIONS and custom methods', async () => {
+    const options = await request({
+      url: 'https://httpbin.org/anything',
+      method: 'OPTIONS',
+      browser: 'chrome_137',
+      timeout: 10000,
+    });
+
+    assert.ok(options.status >= 200 && options.status < 300, 'OPTIONS should succeed');
+    assert.ok(options.headers['allow'], 'OPTIONS response should list allowed methods');
+
+    await assert.rejects(
+      async () => {
+        await request({ url: 'https://httpbin.org/anything', method: 'BAD METHOD' });
+      },
+      {
+        name: 'RequestError',
+        code: 'EINVALIDREQUEST',
+      },
+      'Should reject methods that are not valid tokens'
+    );
+  });
+
+  test('should report the redirect chain', async () => {
+    const response = await request({
+      url: 'https://httpbin.org/redirect/2',
+      browser: 'chrome_137',
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, 'Should end on the final response');
+    assert.strictEqual(response.redirects.length, 2, 'Should record both redirects');
+    assert.ok(
+      response.redirects.every((hop) => hop.status >= 300 && hop.status < 400),
+      'Every hop should be a redirect status'
+    );
+
+    const manual = await request({
+      url: 'https://httpbin.org/redirect/2',
+      browser: 'chrome_137',
+      redirect: 'manual',
+      timeout: 10000,
+    });
+
+    assert.strictEqual(manual.status, 302, 'Manual mode should return the redirect itself');
+    assert.strictEqual(manual.redirects.length, 0, 'Manual mode should not follow');
+
+    await assert.rejects(
+      async () => {
+        await request({
+          url: 'https://httpbin.org/redirect/3',
+          browser: 'chrome_137',
+          maxRedirects: 1,
+          timeout: 10000,
+        });
+      },
+      {
+        name: 'RequestError',
+        code: 'EREDIRECT',
+      },
+      'Should reject when exceeding maxRedirects'
+    );
+
+    await assert.rejects(
+      request({ url: 'https://httpbin.org/redirect/1', maxRedirects: -1 }),
+      { name: 'RequestError', code: 'EINVALIDREQUEST', message: /maxRedirects/ },
+      'Should reject a negative maxRedirects instead of turning redirects off'
+    );
+  });
+
+  test('should parse Set-Cookie attributes', async () => {
+    const setCookies = [
+      'session=abc; Path=/; Max-Age=60; HttpOnly; Secure; SameSite=Lax',
+      'theme=dark; Path=/docs; Expires=Wed, 21 Oct 2037 07:28:00 GMT',
+    ];
+    const query = setCookies.map((value) => `Set-Cookie=${encodeURIComponent(value)}`).join('&');
+
+    const response = await request({
+      url: `https://httpbin.org/response-headers?${query}`,
+      browser: 'chrome_137',
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.cookies.length, 2, 'Should return every Set-Cookie header');
+
+    const [session, theme] = response.cookies;
+
+    assert.strictEqual(session.name, 'session');
+    assert.strictEqual(session.value, 'abc');
+    assert.strictEqual(session.path, '/');
+    assert.strictEqual(session.maxAge, 60);
+    assert.strictEqual(session.httpOnly, true);
+    assert.strictEqual(session.secure, true);
+    assert.strictEqual(session.sameSite, 'Lax');
+    assert.strictEqual(session.raw, setCookies[0], 'Should keep the raw header');
+
+    assert.strictEqual(theme.path, '/docs');
+    assert.ok(theme.expires instanceof Date, 'Expires should be a Date');
+    assert.strictEqual(theme.expires?.toISOString(), '2037-10-21T07:28:00.000Z');
+    assert.strictEqual(theme.httpOnly, false);
+  });
+
+  test('should retry retryable statuses', async () => {
+    const response = await request({
+      url: 'https://httpbin.org/status/503',
+      browser: 'chrome_137',
+      retry: { maxAttempts: 3, baseDelay: 50, jitter: false },
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 503, 'Should return the last response');
+    assert.strictEqual(response.attempts, 3, 'Should use every attempt');
+
+    const post = await request({
+      url: 'https://httpbin.org/status/503',
+      method: 'POST',
+      browser: 'chrome_137',
+      retry: { maxAttempts: 3, baseDelay: 50 },
+      timeout: 10000,
+    });
+
+    assert.strictEqual(post.attempts, 1, 'Should not retry POST by default');
+
+    await assert.rejects(
+      request({ url: 'https://httpbin.org/get', retry: { maxAttempts: 1e9 } }),
+      { name: 'RequestError', code: 'EINVALIDREQUEST', message: /maxAttempts/ },
+      'Should bound the number of attempts'
+    );
+  });
+
+  test('should return binary bodies intact', async () => {
+    const response = await request({
+      url: 'https://httpbin.org/bytes/1024?seed=42',
+      browser: 'chrome_137',
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, 'Should return status 200');
+    assert.ok(Buffer.isBuffer(response.body), 'Body should be a Buffer');
+    assert.strictEqual(response.body.length, 1024, 'Should receive every byte unchanged');
+  });
+
+  test('should stream response bodies in chunks', async () => {
+    const response = await requestStream({
+      url: 'https://httpbin.org/stream-bytes/65536?chunk_size=4096&seed=7',
+      browser: 'chrome_137',
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, 'Should return status 200');
+
+    let received = 0;
+    let chunks = 0;
+
+    for await (const chunk of response.body) {
+      assert.ok(Buffer.isBuffer(chunk), 'Chunks should be Buffers');
+      received += chunk.length;
+      chunks++;
+    }
+
+    assert.strictEqual(received, 65536, 'Should receive the whole body');
+    assert.ok(chunks > 1, 'Should receive more than one chunk');
+    assert.strictEqual(await response.body.read(), null, 'Exhausted body should read null');
+  });
+
+  test('should upload a streamed request body', async () => {
+    async function* chunks() {
+      for (let i = 0; i < 4; i++) {
+        yield Buffer.alloc(1024, i);
+      }
+    }
+
+    const response = await request({
+      url: 'https://httpbin.org/anything',
+      method: 'POST',
+      browser: 'chrome_137',
+      headers: { 'Content-Type': 'application/octet-stream' },
+      body: chunks(),
+      contentLength: 4096,
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, 'Should return status 200');
+    assert.strictEqual(
+      response.json().headers['Content-Length'],
+      '4096',
+      'Should send the declared content length'
+    );
+  });
+
+  test('should send an ordered header list with repeated names', async () => {
+    const response = await request({
+      url: 'https://httpbin.org/headers',
+      browser: 'chrome_137',
+      headers: [
+        ['X-Order-Test', 'one'],
+        ['X-Other', 'value'],
+        ['X-Order-Test', 'two'],
+      ],
+      headerOrder: 'request',
+      timeout: 10000,
+    });
+
+    assert.strictEqual(response.status, 200, 'Should return status 200');
+    const headers = response.json().headers;
+    assert.strictEqual(headers['X-Order-Test'], 'one,two', 'Should send both values in order');
+    assert.strictEqual(headers['X-Other'], 'value', 'Should send the other header');
+    assert.ok(headers['User-Agent'], 'Should keep the profile headers');
+  });
+
+  test('should remove, replace and skip profile headers', async () => {
+    const changed = await request({
+      url: 'https://httpbin.org/headers',
+      browser: 'chrome_137',
+      removeHeaders: ['sec-ch-ua'],
+      replaceHeaders: { 'User-Agent': 'node-wreq-test' },
+      timeout: 10000,
+    });
+
+    const changedHeaders = changed.json().headers;
+    assert.strictEqual(changedHeaders['User-Agent'], 'node-wreq-test', 'Should replace User-Agent');
+    assert.strictEqual(changedHeaders['Sec-Ch-Ua'], undefined, 'Should remove sec-ch-ua');
+    assert.ok(changedHeaders['Sec-Ch-Ua-Mobile'], 'Should keep other profile headers');
+
+    const bare = await request({
+      url: 'https://httpbin.org/headers',
+      browser: 'chrome_137',
+      defaultHeaders: false,
+      headers: { 'X-Only': 'yes' },
+      timeout: 10000,
+    });
+
+    const bareHeaders = bare.json().headers;
+    assert.strictEqual(bareHeaders['X-Only'], 'yes', 'Should send custom headers');
+    assert.strictEqual(bareHeaders['User-Agent'], undefined, 'Should skip the profile headers');
+  });
+
+  test('should reuse a client across requests', async () => {
+    const client = createClient({ browser: 'chrome_137', timeout: 10000 });
+
+    try {
+      const first = await client.get('https://httpbin.org/get');
+      const second = await client.get('https://httpbin.org/get');
+
+      assert.strictEqual(first.status, 200, 'First request should succeed');
+      assert.strictEqual(second.status, 200, 'Second request should succeed');
+
+      await assert.rejects(
+        client.request({ url: 'https://httpbin.org/get', browser: 'firefox_135' } as any),
+        { name: 'RequestError', code: 'EINVALIDREQUEST', message: /browser is fixed/ },
+        'Should reject settings fixed by the client'
+      );
+    } finally {
+      client.close();
+    }
+
+    await assert.rejects(
+      async () => {
+        await client.get('https://httpbin.org/get');
+      },
+      {
+        name: 'RequestError',
+      },
+      'Should reject requests on a closed client'
+    );
+  });
+
+  test('should share cookies through a cookie jar', async () => {
+    const jar = new CookieJar();
+
+    try {
+      // Sets the cookie on the redirect, then echoes it back from /cookies
+      const response = await request({
+        url: 'https://httpbin.org/cookies/set?session=abc',
+        browser: 'chrome_137',
+        cookieJar: jar,
+        timeout: 10000,
+      });
+
+      assert.deepStrictEqual(response.json().cookies, { session: 'abc' });
+      assert.strictEqual(jar.getCookieString('https://httpbin.org/'), 'session=abc');
+
+      jar.setCookie('https://httpbin.org/', { name: 'theme', value: 'dark', path: '/' });
+
+      const restored = CookieJar.deserialize(jar.serialize('netscape'), 'netscape');
+      const echoed = await request({
+        url: 'https://httpbin.org/cookies',
+        browser: 'chrome_137',
+        cookieJar: restored,
+        timeout: 10000,
+      });
+
+      assert.deepStrictEqual(
+        echoed.json().cookies,
+        { session: 'abc', theme: 'dark' },
+        'Cookies should survive a cookies.txt round trip'
+      );
+
+      assert.strictEqual(jar.deleteCookie('session'), 1);
+      jar.clear();
+      assert.deepStrictEqual(jar.getCookies(), [], 'Jar should be empty after clear');
+      restored.close();
+    } finally {
+      jar.close();
+    }
+  });
+
+  test('should bind to a local address and prefer an address family', async () => {
+    const response = await request({
+      url: 'https://httpbin.org/ip',
+      localAddress: '0.0.0.0',
+      dnsOrder: 'ipv4first',
+      timeout: 10000,
+    });
+
+    assert.match(response.json().origin, /^\d+\.\d+\.\d+\.\d+/, 'Should connect over IPv4');
+
+    await assert.rejects(
+      request({ url: 'https://httpbin.org/ip', localAddress: 'eth0' }),
+      { name: 'RequestError', message: /Invalid localAddress/ },
+      'Should reject addresses that are not IPs'
+    );
+  });
+
+  test('should verify certificates unless told to skip', async () => {
+    await assert.rejects(
+      request({ url: 'https://self-signed.badssl.com/', timeout: 10000 }),
+      { name: 'RequestError' },
+      'Should reject a self-signed certificate'
+    );
+
+    const response = await request({
+      url: 'https://self-signed.badssl.com/',
+      tls: { insecureSkipVerify: true },
+      timeout: 10000,
+    });
+    assert.strictEqual(response.status, 200);
+
+    const trusted = await request({
+      url: 'https://httpbin.org/get',
+      tls: { systemCa: true },
+      timeout: 10000,
+    });
+    assert.strictEqual(trusted.status, 200, 'Should keep trusting public roots');
+
+    await assert.rejects(
+      request({ url: 'https://httpbin.org/get', tls: { cert: 'not a certificate' } }),
+      { name: 'RequestError', message: /tls.cert and tls.key/ },
+      'Should require a key with the certificate'
+    );
+
+    await assert.rejects(
+      request({
+        url: 'https://httpbin.org/get',
+        tls: { insecureSkipVerify: true, systemCa: true },
+      }),
+      { name: 'RequestError', code: 'EINVALIDREQUEST' },
+      'Should not ignore roots when skipping verification'
+    );
+  });
+
+  test('should pin hosts and resolve over DNS-over-HTTPS', async () => {
+    // 192.0.2.0/24 is reserved for documentation, nothing answers there
+    await assert.rejects(
+      request({
+        url: 'https://httpbin.org/get',
+        resolve: { 'httpbin.org': '192.0.2.1' },
+        timeout: 3000,
+      }),
+      { name: 'RequestError' },
+      'Should connect to the pinned address'
+    );
+
+    const client = createClient({
+      browser: 'chrome_137',
+      dns: { resolver: 'doh', cacheTtl: 60000 },
+    });
+
+    try {
+      const first = await client.get('https://httpbin.org/get');
+      const second = await client.get('https://httpbin.org/get');
+      assert.strictEqual(first.status, 200);
+      assert.strictEqual(second.status, 200);
+    } finally {
+      client.close();
+    }
+
+    await assert.rejects(
+      request({ url: 'https://httpbin.org/get', resolve: { 'httpbin.org': 'not-an-ip' } }),
+      { name: 'RequestError', message: /Invalid address/ },
+      'Should reject addresses that are not IPs'
+    );
+
+    await assert.rejects(
+      request({ url: 'https://httpbin.org/get', resolve: { 'httpbin.org:443': '192.0.2.1' } }),
+      { name: 'RequestError', code: 'EINVALIDREQUEST', message: /without a port/ },
+      'Should reject keys with a port'
+    );
+
+    const pool = new ProxyPool(['http://127.0.0.1:9']);
+    try {
+      await assert.rejects(
+        request({ url: 'https://httpbin.org/get', proxy: pool, dns: { resolver: 'doh' } }),
+        { name: 'RequestError', code: 'EINVALIDREQUEST' },
+        'Should not send DNS-over-HTTPS queries around a proxy pool'
+      );
+    } finally {
+      pool.close();
+    }
+  });
+
+  test('should reach bypassed hosts without the proxy', async () => {
+    // The proxy is dead, so only a bypassed host can be reached
+    const proxy = {
+      url: 'http://127.0.0.1:9',
+      bypass: ['localhost', '*.example.com', 'httpbin.org'],
+    };
+
+    const response = await request({ url: 'https://httpbin.org/get', proxy, timeout: 10000 });
+    assert.strictEqual(response.status, 200);
+    assert.strictEqual(response.proxy, undefined, 'Should not report a proxy');
+
+    await assert.rejects(
+      request({ url: 'https://example.org/', proxy, timeout: 10000 }),
+      { name: 'RequestError' },
+      'Should route other hosts through the proxy'
+    );
+
+    await assert.rejects(
+      request({ url: 'https://httpbin.org/get', proxy: { ...proxy, bypass: ['10.0.0.0/33'] } }),
+      { code: 'EINVALIDREQUEST' },
+      'Should reject invalid CIDR ranges'
+    );
+  });
+
+  test('should eject failing proxies from a pool', async () => {
+    assert.throws(() => new ProxyPool([]), { code: 'EINVALIDREQUEST' }, 'Should need a proxy');
+
+    // Nothing listens on the discard port, so connecting to the proxy fails
+    const pool = new ProxyPool(['http://127.0.0.1:9'], { maxFailures: 1, cooldown: 60000 });
+
+    try {
+      await assert.rejects(
+        request({ url: 'http://httpbin.org/get', proxy: pool, timeout: 10000 }),
+        { name: 'RequestError' },
+        'Should fail through a dead proxy'
+      );
+
+      const [stats] = pool.stats();
+      assert.strictEqual(stats.url, 'http://127.0.0.1:9');
+      assert.strictEqual(stats.available, false, 'Should eject the proxy');
+      assert.strictEqual(stats.failures, 1);
+      assert.ok(stats.cooldownRemaining! > 0, 'Should report the cooldown');
+
+      await assert.rejects(
+        request({ url: 'http://httpbin.org/get', proxy: pool, timeout: 10000 }),
+        { code: 'EPROXY' },
+        'Should not send requests while every proxy cools down'
+      );
+    } finally {
+      pool.close();
+    }
+  });
 });
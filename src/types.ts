@@ -1,3 +1,5 @@
+import type { CookieJar, ProxyPool } from './node-wreq';
+
 /**
  * Browser profile names supported
  */
@@ -108,12 +110,202 @@ export type BrowserProfile =
   | 'okhttp_4_9'
   | 'okhttp_4_10'
   | 'okhttp_4_12'
-  | 'okhttp_5';
+  | 'okhttp_5'
+  // Any built-in profile, picked at random
+  | 'random'
+  // Custom profiles registered with defineProfile()
+  | (string & {});
+
+/**
+ * Picks a random built-in profile per request (or per client), restricted
+ * by the filters and, with `weights`, following a distribution such as
+ * browser market share. Draws come from a shared RNG that
+ * `seedProfileSelection()` makes reproducible.
+ */
+export interface ProfileSelector {
+  /** e.g. 'chrome', 'safari', 'firefox' */
+  family?: string;
+  platform?: ProfilePlatform;
+  /** Lowest major version, inclusive */
+  minVersion?: number;
+  /** Highest major version, inclusive */
+  maxVersion?: number;
+  /**
+   * Relative weights by profile name or family; a family's weight is shared
+   * evenly between its profiles, e.g. `{ chrome: 65, safari: 20, firefox: 15 }`.
+   * Custom profiles can only be weighted when no filter is set
+   */
+  weights?: Record<string, number>;
+}
+
+/**
+ * Operating system a built-in profile can be paired with
+ */
+export type EmulationOS = 'windows' | 'macos' | 'linux' | 'android' | 'ios';
+
+/**
+ * Device type a browser profile impersonates
+ */
+export type ProfilePlatform = 'desktop' | 'ios' | 'ipad' | 'android';
+
+/**
+ * What a browser profile impersonates, from `describeProfile()`
+ */
+export interface ProfileInfo {
+  name: string;
+  /** 'chrome', 'edge', 'safari', 'firefox', 'opera', 'okhttp' or 'custom' */
+  family: string;
+  /** Browser version, e.g. '18.1.1' (not set for custom profiles) */
+  version?: string;
+  /** Not set for custom profiles */
+  platform?: ProfilePlatform;
+  /** Private browsing mode */
+  private: boolean;
+  /** Whether HTTP/2 is offered ahead of HTTP/1.1 */
+  http2: boolean;
+  /** Default User-Agent header */
+  userAgent?: string;
+  /** Registered with `defineProfile()` */
+  custom: boolean;
+  /**
+   * JA3 string of the profile's ClientHello, without GREASE values. Profiles that
+   * permute their extensions (Chrome, Edge) give a different one on every connection.
+   */
+  ja3: string;
+  /** MD5 of `ja3` */
+  ja3Hash: string;
+  /** JA4 fingerprint, stable across connections */
+  ja4: string;
+  /**
+   * Akamai HTTP/2 fingerprint: SETTINGS, WINDOW_UPDATE, PRIORITY frames and
+   * pseudo-header order (not set for profiles that don't offer HTTP/2)
+   */
+  akamai?: string;
+}
+
+/**
+ * TLS ClientHello settings of a custom profile
+ */
+export interface TlsProfileSpec {
+  /** OpenSSL cipher string, e.g. 'TLS_AES_128_GCM_SHA256:ECDHE-ECDSA-AES128-GCM-SHA256' */
+  cipherList?: string;
+  /** Supported groups, e.g. 'X25519MLKEM768:X25519:P-256:P-384' */
+  curves?: string;
+  /** Signature algorithms, e.g. 'ecdsa_secp256r1_sha256:rsa_pss_rsae_sha256' */
+  sigalgs?: string;
+  /** ALPN protocols in preference order */
+  alpn?: Array<'h2' | 'http/1.1'>;
+  /** Fixed extension order by IANA extension ID */
+  extensionOrder?: number[];
+  /** Shuffle the extension order on every handshake, like Chrome */
+  permuteExtensions?: boolean;
+  grease?: boolean;
+  echGrease?: boolean;
+  preSharedKey?: boolean;
+  ocspStapling?: boolean;
+  signedCertTimestamps?: boolean;
+  certCompression?: Array<'brotli' | 'zlib' | 'zstd'>;
+  minVersion?: '1.0' | '1.1' | '1.2' | '1.3';
+  maxVersion?: '1.0' | '1.1' | '1.2' | '1.3';
+}
+
+/**
+ * HTTP/2 SETTINGS name, as used in `Http2ProfileSpec.settingsOrder`
+ */
+export type Http2Setting =
+  | 'headerTableSize'
+  | 'enablePush'
+  | 'maxConcurrentStreams'
+  | 'initialWindowSize'
+  | 'maxFrameSize'
+  | 'maxHeaderListSize'
+  | 'enableConnectProtocol'
+  | 'noRfc7540Priorities';
 
 /**
- * HTTP method types
+ * HTTP/2 connection settings of a custom profile
  */
-export type HttpMethod = 'GET' | 'POST' | 'PUT' | 'DELETE' | 'PATCH' | 'HEAD';
+export interface Http2ProfileSpec {
+  headerTableSize?: number;
+  enablePush?: boolean;
+  maxConcurrentStreams?: number;
+  initialWindowSize?: number;
+  maxFrameSize?: number;
+  maxHeaderListSize?: number;
+  /** Order of the SETTINGS frame entries */
+  settingsOrder?: Http2Setting[];
+  /** Connection window size announced with the first WINDOW_UPDATE */
+  connectionWindowSize?: number;
+  /** Pseudo-header order, e.g. [':method', ':authority', ':scheme', ':path'] */
+  pseudoHeaderOrder?: Array<':method' | ':authority' | ':scheme' | ':path' | ':protocol'>;
+  /** Priority sent with the HEADERS frame */
+  priority?: { streamDependency?: number; weight?: number; exclusive?: boolean };
+}
+
+/**
+ * Description of a custom browser profile for `defineProfile()`.
+ * Settings left out use wreq's defaults.
+ */
+export interface ProfileSpec {
+  tls?: TlsProfileSpec;
+  http2?: Http2ProfileSpec;
+  /** Default headers, sent in the given order and casing */
+  headers?: Record<string, string> | Array<[string, string]>;
+}
+
+/**
+ * HTTP method types.
+ * Any valid method token is accepted (e.g. 'PROPFIND', 'REPORT' for WebDAV/CalDAV);
+ * methods are sent upper-cased.
+ */
+export type HttpMethod =
+  | 'GET'
+  | 'POST'
+  | 'PUT'
+  | 'DELETE'
+  | 'PATCH'
+  | 'HEAD'
+  | 'OPTIONS'
+  | 'TRACE'
+  | 'CONNECT'
+  | (string & {});
+
+/**
+ * Request headers: a plain object (arrays send one header per value) or
+ * an ordered list of `[name, value]` pairs, which keeps the given order,
+ * casing and repeated names
+ */
+export type RequestHeaders = Record<string, string | string[]> | Array<[string, string]>;
+
+/**
+ * How custom headers are ordered against the browser profile's defaults.
+ * - `'profile'`: keep the profile's order; headers it doesn't know are appended
+ * - `'request'`: send custom headers first, in the given order and casing,
+ *   followed by the remaining profile headers
+ */
+export type HeaderOrder = 'profile' | 'request';
+
+/**
+ * Request body read from a file on disk while it is being sent
+ */
+export interface FileBody {
+  /**
+   * Path of the file to upload
+   */
+  path: string;
+}
+
+/**
+ * Supported request body types.
+ * Files and async iterables (Node.js streams, web ReadableStreams, async
+ * generators) are streamed without being loaded into memory.
+ */
+export type RequestBody =
+  | string
+  | Buffer
+  | Uint8Array
+  | FileBody
+  | AsyncIterable<Uint8Array | string>;
 
 /**
  * Request options for making HTTP requests with browser impersonation
@@ -128,7 +320,21 @@ export interface RequestOptions {
    * Browser profile to impersonate
    * @default 'chrome_137'
    */
-  browser?: BrowserProfile;
+  browser?: BrowserProfile | ProfileSelector;
+
+  /**
+   * Resolve an unknown `browser` to the nearest known version of the same
+   * browser (e.g. 'chrome_139' to 'chrome_138') instead of throwing
+   * @default false
+   */
+  lenientBrowser?: boolean;
+
+  /**
+   * Operating system the profile claims in its User-Agent and client hints,
+   * e.g. Chrome on 'macos' instead of the profile's default. Only for
+   * built-in profiles.
+   */
+  os?: EmulationOS;
 
   /**
    * HTTP method
@@ -140,23 +346,646 @@ export interface RequestOptions {
    * Additional headers to send with the request
    * Browser-specific headers will be automatically added
    */
-  headers?: Record<string, string>;
+  headers?: RequestHeaders;
 
   /**
-   * Request body (for POST, PUT, PATCH requests)
+   * Where custom headers go relative to the profile's headers
+   * @default 'profile'
    */
-  body?: string;
+  headerOrder?: HeaderOrder;
 
   /**
-   * Proxy URL (e.g., 'http://proxy.example.com:8080')
+   * Send the browser profile's default headers (User-Agent, Accept,
+   * sec-ch-ua, ...). `false` sends only your own headers; the TLS and
+   * HTTP/2 fingerprint is unaffected.
+   * @default true
    */
-  proxy?: string;
+  defaultHeaders?: boolean;
+
+  /**
+   * Profile headers to leave out, e.g. `['sec-ch-ua-platform']`
+   */
+  removeHeaders?: string[];
+
+  /**
+   * Profile headers to replace while keeping their position in the
+   * profile's order (headers the profile doesn't send are appended)
+   */
+  replaceHeaders?: RequestHeaders;
+
+  /**
+   * Request body (for POST, PUT, PATCH requests).
+   * Buffers and Uint8Arrays are sent byte-for-byte; files and async
+   * iterables are streamed.
+   */
+  body?: RequestBody;
+
+  /**
+   * Declared length of a streamed body in bytes.
+   * Without it, async iterable bodies use chunked transfer encoding
+   * (file bodies use the file size).
+   */
+  contentLength?: number;
+
+  /**
+   * Proxy URL (e.g., 'http://proxy.example.com:8080'), per-scheme proxies with
+   * credentials and a bypass list, or a {@link ProxyPool} to pick one from
+   */
+  proxy?: string | ProxyOptions | ProxyPool;
+
+  /**
+   * Request timeout in milliseconds (alias of `totalTimeout`)
+   * @default 30000 (or the client timeout when using a client)
+   */
+  timeout?: number;
+
+  /**
+   * Time for the whole request, redirects and body included, in milliseconds.
+   * Takes precedence over `timeout`.
+   */
+  totalTimeout?: number;
+
+  /**
+   * Time to establish a connection (TCP connect and TLS handshake) in milliseconds.
+   * Set on the client when using one.
+   */
+  connectTimeout?: number;
+
+  /**
+   * Local IPv4 or IPv6 address outgoing connections are bound to,
+   * e.g. one of several egress IPs.
+   * Set on the client when using one.
+   */
+  localAddress?: string;
+
+  /**
+   * Network interface outgoing connections are bound to (e.g. 'eth1').
+   * Linux, Android, macOS and iOS only.
+   * Set on the client when using one.
+   */
+  interface?: string;
+
+  /**
+   * Order resolved addresses are tried in: 'ipv4first' / 'ipv6first' prefer
+   * a family and fall back to the other.
+   * Set on the client when using one.
+   * @default 'verbatim'
+   */
+  dnsOrder?: DnsOrder;
+
+  /**
+   * Fixed addresses per host, used instead of a DNS lookup (like curl `--resolve`).
+   * Keys are host names without a port; the addresses apply to every port of the host.
+   * Set on the client when using one.
+   * @example { 'example.com': '203.0.113.7' }
+   */
+  resolve?: ResolveMap;
+
+  /**
+   * Resolver used for host names, and the DNS cache.
+   * Set on the client when using one.
+   */
+  dns?: DnsOptions;
+
+  /**
+   * Extra trusted roots, client certificate for mutual TLS, and verification toggle.
+   * Set on the client when using one.
+   */
+  tls?: TlsOptions;
+
+  /**
+   * Time to wait for the response headers after sending, per redirect hop, in milliseconds
+   */
+  responseHeaderTimeout?: number;
+
+  /**
+   * Maximum idle time between two chunks of the response body in milliseconds
+   */
+  readTimeout?: number;
+
+  /**
+   * Redirect handling: follow them, return the 3xx response as-is, or reject
+   * @default 'follow'
+   */
+  redirect?: 'follow' | 'manual' | 'error';
+
+  /**
+   * Maximum number of redirects to follow before rejecting
+   * @default 10
+   */
+  maxRedirects?: number;
+
+  /**
+   * Cookie jar to send cookies from and store received cookies in.
+   * Requests made through a client use the client's jar by default.
+   */
+  cookieJar?: CookieJar;
+
+  /**
+   * Retry failed attempts: `true` for the defaults, a number of attempts (1 to 10), or a policy
+   */
+  retry?: boolean | number | RetryOptions;
+
+  /**
+   * Cancels the request when aborted; the promise (or a streamed body read)
+   * rejects with an `AbortError` and the connection is closed
+   */
+  signal?: AbortSignal;
+}
+
+/**
+ * Retry policy of a request
+ */
+export interface RetryOptions {
+  /**
+   * Attempts in total, the first one included (at most 10)
+   * @default 3
+   */
+  maxAttempts?: number;
+
+  /**
+   * Delay before the first retry in milliseconds, doubled for each further retry
+   * @default 200
+   */
+  baseDelay?: number;
+
+  /**
+   * Upper bound for a retry delay in milliseconds. A `Retry-After` asking for
+   * longer ends the retries.
+   * @default 10000
+   */
+  maxDelay?: number;
+
+  /**
+   * Wait a random time between half and all of the delay
+   * @default true
+   */
+  jitter?: boolean;
+
+  /**
+   * Response statuses that are retried (429 and 503 honour `Retry-After`)
+   * @default [408, 429, 500, 502, 503, 504]
+   */
+  statuses?: number[];
+
+  /**
+   * Retry timeouts and refused or reset connections
+   * @default true
+   */
+  retryOnError?: boolean;
+
+  /**
+   * Also retry methods that are not idempotent, such as POST and PATCH
+   * @default false
+   */
+  retryNonIdempotent?: boolean;
+}
+
+/**
+ * Options for `init`, applied to the shared native runtime
+ */
+export interface InitOptions {
+  /**
+   * Number of worker threads running HTTP requests (1 to 1024)
+   * @default number of CPU cores
+   */
+  workerThreads?: number;
+}
 
+/**
+ * Options for a reusable client created with `createClient`
+ */
+export interface ClientOptions {
   /**
-   * Request timeout in milliseconds
+   * Browser profile to impersonate for every request made through the client
+   * @default 'chrome_137'
+   */
+  browser?: BrowserProfile | ProfileSelector;
+
+  /**
+   * Resolve an unknown `browser` to the nearest known version of the same
+   * browser (e.g. 'chrome_139' to 'chrome_138') instead of throwing
+   * @default false
+   */
+  lenientBrowser?: boolean;
+
+  /**
+   * Operating system the profile claims in its User-Agent and client hints,
+   * e.g. Chrome on 'macos' instead of the profile's default. Only for
+   * built-in profiles.
+   */
+  os?: EmulationOS;
+
+  /**
+   * Proxy URL (e.g., 'http://proxy.example.com:8080'), per-scheme proxies with
+   * credentials and a bypass list, or a {@link ProxyPool} to pick one from
+   */
+  proxy?: string | ProxyOptions | ProxyPool;
+
+  /**
+   * Default request timeout in milliseconds (alias of `totalTimeout`)
    * @default 30000
    */
   timeout?: number;
+
+  /**
+   * Default time for the whole request in milliseconds
+   */
+  totalTimeout?: number;
+
+  /**
+   * Time to establish a connection (TCP connect and TLS handshake) in milliseconds
+   */
+  connectTimeout?: number;
+
+  /**
+   * Local IPv4 or IPv6 address outgoing connections are bound to,
+   * e.g. one of several egress IPs
+   */
+  localAddress?: string;
+
+  /**
+   * Network interface outgoing connections are bound to (e.g. 'eth1').
+   * Linux, Android, macOS and iOS only.
+   */
+  interface?: string;
+
+  /**
+   * Order resolved addresses are tried in: 'ipv4first' / 'ipv6first' prefer
+   * a family and fall back to the other
+   * @default 'verbatim'
+   */
+  dnsOrder?: DnsOrder;
+
+  /**
+   * Fixed addresses per host, used instead of a DNS lookup (like curl `--resolve`).
+   * Keys are host names without a port; the addresses apply to every port of the host.
+   * @example { 'example.com': '203.0.113.7' }
+   */
+  resolve?: ResolveMap;
+
+  /**
+   * Resolver used for host names, and the DNS cache
+   */
+  dns?: DnsOptions;
+
+  /**
+   * Extra trusted roots, client certificate for mutual TLS, and verification toggle
+   */
+  tls?: TlsOptions;
+
+  /**
+   * Default time to wait for response headers in milliseconds
+   */
+  responseHeaderTimeout?: number;
+
+  /**
+   * Default maximum idle time between two body chunks in milliseconds
+   */
+  readTimeout?: number;
+
+  /**
+   * Cookie jar shared by every request of the client (a private one is used otherwise)
+   */
+  cookieJar?: CookieJar;
+}
+
+/**
+ * Request options for requests made through a client.
+ * Browser, proxy, connect timeout, local binding, DNS and TLS settings are fixed when the
+ * client is created; passing them anyway rejects with `EINVALIDREQUEST`.
+ */
+export type ClientRequestOptions = Omit<
+  RequestOptions,
+  | 'browser'
+  | 'lenientBrowser'
+  | 'os'
+  | 'proxy'
+  | 'connectTimeout'
+  | 'localAddress'
+  | 'interface'
+  | 'dnsOrder'
+  | 'resolve'
+  | 'dns'
+  | 'tls'
+>;
+
+/**
+ * A redirect response seen on the way to the final response
+ */
+export interface RedirectHop {
+  /**
+   * URL that answered with the redirect
+   */
+  url: string;
+
+  /**
+   * Redirect status code (301, 302, 303, 307, 308)
+   */
+  status: number;
+
+  /**
+   * Resolved URL the redirect pointed to
+   */
+  location: string;
+
+  /**
+   * Raw Set-Cookie headers sent with the redirect
+   */
+  setCookies: string[];
+}
+
+/**
+ * Cookie parsed from a Set-Cookie response header
+ */
+export interface Cookie {
+  name: string;
+  value: string;
+
+  /**
+   * Domain attribute (without leading dot), absent for host-only cookies
+   */
+  domain?: string;
+
+  path?: string;
+
+  /**
+   * Expires attribute
+   */
+  expires?: Date;
+
+  /**
+   * Max-Age attribute in seconds (takes precedence over `expires`)
+   */
+  maxAge?: number;
+
+  secure: boolean;
+  httpOnly: boolean;
+  sameSite?: 'Strict' | 'Lax' | 'None';
+
+  /**
+   * Set-Cookie header value as received
+   */
+  raw: string;
+}
+
+/**
+ * Cookie held by a {@link CookieJar}, with domain, path and expiry resolved
+ */
+export interface StoredCookie {
+  name: string;
+  value: string;
+
+  /**
+   * Domain without leading dot
+   */
+  domain: string;
+
+  /**
+   * Only sent to `domain` itself, not to its subdomains
+   */
+  hostOnly: boolean;
+
+  path: string;
+
+  /**
+   * Expiry, absent for session cookies
+   */
+  expires?: Date;
+
+  secure: boolean;
+  httpOnly: boolean;
+  sameSite?: 'Strict' | 'Lax' | 'None';
+}
+
+/**
+ * Cookie to add to a {@link CookieJar} with `setCookie`
+ */
+export interface CookieInit {
+  name: string;
+  value: string;
+  domain?: string;
+  path?: string;
+  expires?: Date;
+
+  /**
+   * Lifetime in seconds (takes precedence over `expires`)
+   */
+  maxAge?: number;
+
+  secure?: boolean;
+  httpOnly?: boolean;
+  sameSite?: 'Strict' | 'Lax' | 'None';
+}
+
+/**
+ * Serialization formats supported by {@link CookieJar}
+ */
+export type CookieFormat = 'json' | 'netscape';
+
+/**
+ * Proxy settings beyond a single URL
+ *
+ * @example
+ * ```typescript
+ * {
+ *   https: 'http://proxy.example.com:8080',
+ *   username: 'user',
+ *   password: 'p@ss:word',
+ *   bypass: ['localhost', '*.internal.example.com', '10.0.0.0/8'],
+ * }
+ * ```
+ */
+export interface ProxyOptions {
+  /**
+   * Proxy for every scheme without a more specific one
+   */
+  url?: string;
+
+  /**
+   * Proxy for `http://` (and `ws://`) requests
+   */
+  http?: string;
+
+  /**
+   * Proxy for `https://` (and `wss://`) requests
+   */
+  https?: string;
+
+  /**
+   * Credentials for the proxy, taking precedence over the ones in its URL.
+   * No URL encoding needed.
+   */
+  username?: string;
+  password?: string;
+
+  /**
+   * `Proxy-Authorization` header value sent as-is, e.g. 'Bearer <token>'
+   */
+  authorization?: string;
+
+  /**
+   * Hosts reached without the proxy: 'example.com' (and its subdomains),
+   * '*.example.com' (subdomains only), IP addresses, CIDR ranges like
+   * '10.0.0.0/8', or '*' for every host. A string is split on commas like `NO_PROXY`.
+   */
+  bypass?: string[] | string;
+
+  /**
+   * Fill in missing proxies from `HTTP_PROXY`, `HTTPS_PROXY` and `ALL_PROXY`,
+   * and extend the bypass list with `NO_PROXY` (lowercase names work too)
+   * @default false
+   */
+  fromEnv?: boolean;
+}
+
+/**
+ * Order resolved addresses are tried in, as with Node's `dns.lookup`
+ */
+export type DnsOrder = 'verbatim' | 'ipv4first' | 'ipv6first';
+
+/**
+ * TLS settings: trusted roots, client certificate and verification
+ */
+export interface TlsOptions {
+  /**
+   * Extra root certificates (PEM), trusted next to the bundled Mozilla roots.
+   * Each entry may hold several certificates.
+   */
+  ca?: string | Buffer | Array<string | Buffer>;
+
+  /**
+   * Also trust the operating system's certificate store (the Keychain on macOS, the
+   * Windows certificate store, the CA bundle elsewhere)
+   * @default false
+   */
+  systemCa?: boolean;
+
+  /**
+   * Client certificate (chain) for mutual TLS, in PEM. Requires `key`.
+   */
+  cert?: string | Buffer;
+
+  /**
+   * Private key of `cert`, in PEM (PKCS#8, PKCS#1 RSA or SEC1 EC)
+   */
+  key?: string | Buffer;
+
+  /**
+   * Client certificate and key as a PKCS#12 archive, instead of `cert` and `key`
+   */
+  pfx?: Buffer;
+
+  /**
+   * Passphrase of `pfx`
+   */
+  passphrase?: string;
+
+  /**
+   * Accept any server certificate and host name. Only for test environments:
+   * connections are open to interception. Can't be combined with `ca` or `systemCa`.
+   * @default false
+   */
+  insecureSkipVerify?: boolean;
+}
+
+/**
+ * Fixed addresses per host (no port), one or several each
+ */
+export type ResolveMap = Record<string, string | string[]>;
+
+/**
+ * Host name resolution settings
+ */
+export interface DnsOptions {
+  /**
+   * Resolver to look host names up with:
+   * - 'system': the operating system's resolver
+   * - 'hickory': a built-in resolver, using `nameservers` or the system configuration
+   * - 'doh': DNS-over-HTTPS, sent with the same browser profile, `tls` settings, binding
+   *   and proxy as the requests (so lookups don't leak outside the proxy). Each query may
+   *   take 5s.
+   *   Can't be combined with a {@link ProxyPool}.
+   * @default 'system'
+   */
+  resolver?: 'system' | 'hickory' | 'doh';
+
+  /**
+   * Nameservers for the 'hickory' resolver, as 'ip' or 'ip:port' ('[ipv6]:port')
+   * @example ['1.1.1.1', '8.8.8.8:53']
+   */
+  nameservers?: string[];
+
+  /**
+   * JSON API endpoint of the 'doh' resolver
+   * @default 'https://cloudflare-dns.com/dns-query'
+   */
+  dohUrl?: string;
+
+  /**
+   * Keep answers in a cache shared by every request of the client, for at most
+   * this many milliseconds (and no longer than the record's TTL). No cache when unset.
+   */
+  cacheTtl?: number;
+}
+
+/**
+ * How a {@link ProxyPool} picks the proxy for the next request:
+ * - `round-robin`: each available proxy in turn
+ * - `random`: any available proxy
+ * - `sticky-host`: the same proxy for every request to a host, until it is ejected
+ * - `least-failures`: the proxy that failed least often
+ */
+export type ProxyStrategy = 'round-robin' | 'random' | 'sticky-host' | 'least-failures';
+
+/**
+ * Rotation and ejection settings of a {@link ProxyPool}
+ */
+export interface ProxyPoolOptions {
+  /**
+   * @default 'round-robin'
+   */
+  strategy?: ProxyStrategy;
+
+  /**
+   * Consecutive failures (refused or timed out connections, CONNECT and proxy TLS
+   * errors, or a 407 response) after which a proxy is ejected. Errors from the target,
+   * such as a rejected TLS handshake, don't count
+   * @default 3
+   */
+  maxFailures?: number;
+
+  /**
+   * Milliseconds an ejected proxy sits out. Once back, a single failure
+   * ejects it again.
+   * @default 30000
+   */
+  cooldown?: number;
+}
+
+/**
+ * Health of one proxy of a {@link ProxyPool}
+ */
+export interface ProxyStats {
+  url: string;
+
+  /**
+   * Not ejected, so requests may pick it
+   */
+  available: boolean;
+
+  /**
+   * Failures since its last success
+   */
+  consecutiveFailures: number;
+
+  failures: number;
+  successes: number;
+
+  /**
+   * Milliseconds until an ejected proxy is tried again
+   */
+  cooldownRemaining?: number;
 }
 
 /**
@@ -174,21 +1003,101 @@ export interface Response {
   headers: Record<string, string>;
 
   /**
-   * Response body as string
+   * Raw response body
    */
-  body: string;
+  body: Buffer;
 
   /**
-   * Cookies set by the server
+   * Cookies set by the server, one per Set-Cookie header in order
    */
-  cookies: Record<string, string>;
+  cookies: Cookie[];
 
   /**
    * Final URL after redirects
    */
   url: string;
+
+  /**
+   * Redirects followed before the final response, in order
+   */
+  redirects: RedirectHop[];
+
+  /**
+   * Attempts made, retries included
+   */
+  attempts: number;
+
+  /**
+   * Browser profile the request impersonated (the one picked when
+   * `browser` is 'random' or a selector)
+   */
+  profile: string;
+
+  /**
+   * Proxy the request went through (the one picked when `proxy` is a pool)
+   */
+  proxy?: string;
+
+  /**
+   * Decode the body as UTF-8 text
+   */
+  text(): string;
+
+  /**
+   * Decode the body as UTF-8 and parse it as JSON
+   */
+  json<T = any>(): T;
+}
+
+/**
+ * Response returned from streaming requests: resolved as soon as headers
+ * arrive, with the body read chunk by chunk
+ */
+export interface StreamingResponse extends Omit<Response, 'body' | 'text' | 'json'> {
+  /**
+   * Response body, consumed with `for await` or `read()`
+   */
+  body: AsyncIterable<Buffer> & {
+    /**
+     * Read the next chunk, or `null` once the body is complete
+     */
+    read(): Promise<Buffer | null>;
+
+    /**
+     * Stop reading and close the connection
+     */
+    cancel(): void;
+  };
 }
 
+/**
+ * Internal request body sink handle returned from native binding
+ */
+export interface NativeBodySink {
+  _id: number;
+}
+
+/**
+ * Internal body stream handle returned from native binding
+ */
+export interface NativeBodyStream {
+  _id: number;
+}
+
+/**
+ * Internal abort token handle returned from native binding
+ */
+export interface NativeAbortToken {
+  _id: number;
+}
+
+/**
+ * Response object as returned from native binding (before helpers are attached)
+ */
+export type NativeResponse = Omit<Response, 'text' | 'json'> & {
+  _stream?: NativeBodyStream;
+};
+
 /**
  * WebSocket options for creating a connection
  */
@@ -202,17 +1111,98 @@ export interface WebSocketOptions {
    * Browser profile to impersonate
    * @default 'chrome_137'
    */
-  browser?: BrowserProfile;
+  browser?: BrowserProfile | ProfileSelector;
+
+  /**
+   * Resolve an unknown `browser` to the nearest known version of the same
+   * browser (e.g. 'chrome_139' to 'chrome_138') instead of throwing
+   * @default false
+   */
+  lenientBrowser?: boolean;
+
+  /**
+   * Operating system the profile claims in its User-Agent and client hints,
+   * e.g. Chrome on 'macos' instead of the profile's default. Only for
+   * built-in profiles.
+   */
+  os?: EmulationOS;
 
   /**
    * Additional headers to send with the WebSocket upgrade request
    */
-  headers?: Record<string, string>;
+  headers?: RequestHeaders;
 
   /**
-   * Proxy URL (e.g., 'http://proxy.example.com:8080')
+   * Where custom headers go relative to the profile's headers
+   * @default 'profile'
    */
-  proxy?: string;
+  headerOrder?: HeaderOrder;
+
+  /**
+   * Send the browser profile's default headers (User-Agent, Accept,
+   * sec-ch-ua, ...). `false` sends only your own headers; the TLS and
+   * HTTP/2 fingerprint is unaffected.
+   * @default true
+   */
+  defaultHeaders?: boolean;
+
+  /**
+   * Profile headers to leave out, e.g. `['sec-ch-ua-platform']`
+   */
+  removeHeaders?: string[];
+
+  /**
+   * Profile headers to replace while keeping their position in the
+   * profile's order (headers the profile doesn't send are appended)
+   */
+  replaceHeaders?: RequestHeaders;
+
+  /**
+   * Proxy URL (e.g., 'http://proxy.example.com:8080'), per-scheme proxies with
+   * credentials and a bypass list, or a {@link ProxyPool} to pick one from
+   */
+  proxy?: string | ProxyOptions | ProxyPool;
+
+  /**
+   * Local IPv4 or IPv6 address outgoing connections are bound to,
+   * e.g. one of several egress IPs
+   */
+  localAddress?: string;
+
+  /**
+   * Network interface outgoing connections are bound to (e.g. 'eth1').
+   * Linux, Android, macOS and iOS only.
+   */
+  interface?: string;
+
+  /**
+   * Order resolved addresses are tried in: 'ipv4first' / 'ipv6first' prefer
+   * a family and fall back to the other
+   * @default 'verbatim'
+   */
+  dnsOrder?: DnsOrder;
+
+  /**
+   * Fixed addresses per host, used instead of a DNS lookup (like curl `--resolve`).
+   * Keys are host names without a port; the addresses apply to every port of the host.
+   * @example { 'example.com': '203.0.113.7' }
+   */
+  resolve?: ResolveMap;
+
+  /**
+   * Resolver used for host names, and the DNS cache
+   */
+  dns?: DnsOptions;
+
+  /**
+   * Extra trusted roots, client certificate for mutual TLS, and verification toggle
+   */
+  tls?: TlsOptions;
+
+  /**
+   * Cookie jar to send cookies from with the upgrade request and store received cookies in
+   */
+  cookieJar?: CookieJar;
 
   /**
    * Callback for incoming messages (required)
@@ -230,16 +1220,133 @@ export interface WebSocketOptions {
   onError?: (error: string) => void;
 }
 
+/**
+ * Internal client handle returned from native binding
+ */
+export interface NativeClient {
+  _id: number;
+}
+
+/**
+ * Internal cookie jar handle returned from native binding
+ */
+export interface NativeCookieJar {
+  _id: number;
+}
+
+/**
+ * Internal proxy pool handle returned from native binding
+ */
+export interface NativeProxyPool {
+  _id: number;
+}
+
 /**
  * Internal WebSocket connection object returned from native binding
  */
 export interface NativeWebSocketConnection {
   _id: number;
+  proxy?: string;
+}
+
+/**
+ * Failure category of a {@link RequestError}
+ */
+export type ErrorCode =
+  | 'ETIMEDOUT'
+  | 'ECONNREFUSED'
+  | 'ECONNRESET'
+  | 'ENOTFOUND'
+  | 'ETLS'
+  | 'EPROXY'
+  | 'EINVALIDURL'
+  | 'EINVALIDREQUEST'
+  | 'EREDIRECT'
+  | 'EBODY'
+  | 'EWEBSOCKET'
+  | 'ABORT_ERR'
+  | 'EREQUEST';
+
+/**
+ * Request phase a timeout fired in
+ */
+export type TimeoutPhase = 'connect' | 'responseHeader' | 'read' | 'total';
+
+export interface RequestErrorDetails {
+  code?: ErrorCode;
+  url?: string;
+  method?: string;
+  cause?: unknown;
+  phase?: TimeoutPhase;
 }
 
 export class RequestError extends Error {
-  constructor(message: string) {
+  /**
+   * Failure category, e.g. `'ETIMEDOUT'` or `'ENOTFOUND'`
+   */
+  code: ErrorCode;
+
+  /**
+   * URL of the failed request, when known
+   */
+  url?: string;
+
+  /**
+   * HTTP method of the failed request, when known
+   */
+  method?: string;
+
+  /**
+   * Innermost error reported by the native layer
+   */
+  cause?: unknown;
+
+  /**
+   * Phase that ran out of time, for `'ETIMEDOUT'` errors
+   */
+  phase?: TimeoutPhase;
+
+  constructor(message: string, details: RequestErrorDetails = {}) {
     super(message);
     this.name = 'RequestError';
+    this.code = details.code ?? 'EREQUEST';
+    this.url = details.url;
+    this.method = details.method;
+    this.cause = details.cause;
+    this.phase = details.phase;
+  }
+
+  /**
+   * Wrap an error thrown by the native binding, keeping its details
+   */
+  static from(error: unknown): RequestError {
+    if (error instanceof RequestError) {
+      return error;
+    }
+
+    if (error instanceof Error) {
+      const details = error as Error & RequestErrorDetails;
+      const ErrorClass = details.code === 'ABORT_ERR' ? AbortError : RequestError;
+
+      return new ErrorClass(error.message, {
+        code: details.code,
+        url: details.url,
+        method: details.method,
+        cause: details.cause,
+        phase: details.phase,
+      });
+    }
+
+    return new RequestError(String(error));
+  }
+}
+
+/**
+ * Rejection of a request cancelled through its `signal`
+ */
+export class AbortError extends RequestError {
+  constructor(message = 'This operation was aborted', details: RequestErrorDetails = {}) {
+    super(message, { ...details, code: 'ABORT_ERR' });
+    this.name = 'AbortError';
   }
 }